@@ -0,0 +1,141 @@
+//! Writes tests/LegacyRegionTest.ptf, a Pro Tools 7 session built block by block.
+//!
+//! No Pro Tools 5-7 session was available to test with, so the fixture is generated
+//! from the layout ptformat reads for these versions: big endian, xor type 0x01, two
+//! audio files, three regions and a mono and a stereo track placed with the pre-v8
+//! AUDIO_Region_Track_Full_Map.
+
+const XOR_VALUE: u8 = 0x35;
+
+fn u16(v: u16) -> Vec<u8> {
+    v.to_be_bytes().to_vec()
+}
+
+fn u32(v: u32) -> Vec<u8> {
+    v.to_be_bytes().to_vec()
+}
+
+fn u64(v: u64) -> Vec<u8> {
+    v.to_be_bytes().to_vec()
+}
+
+fn block(block_type: u16, content_type: u16, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![0x5a];
+    bytes.extend(u16(block_type));
+    bytes.extend(u32(payload.len() as u32 + 2));
+    bytes.extend(u16(content_type));
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+fn string(s: &str) -> Vec<u8> {
+    let mut bytes = u32(s.len() as u32);
+    bytes.extend_from_slice(s.as_bytes());
+    bytes
+}
+
+// Big endian three point value: the byte lengths of start, length and offset in the
+// upper nibbles, then offset, length and start in as many bytes as they need
+fn three_point(offset: u64, len: u64, start: u64) -> Vec<u8> {
+    let num_bytes = |v: u64| (0..8).find(|n| v >> (8 * n) == 0).unwrap_or(8);
+    let (offset_bytes, len_bytes, start_bytes) = (num_bytes(offset), num_bytes(len), num_bytes(start));
+    let mut bytes = vec![0, 0, (start_bytes << 4) as u8, (len_bytes << 4) as u8, (offset_bytes << 4) as u8];
+    for &(value, n) in &[(offset, offset_bytes), (len, len_bytes), (start, start_bytes)] {
+        bytes.extend_from_slice(&value.to_be_bytes()[8 - n..]);
+    }
+    bytes
+}
+
+fn session() -> Vec<u8> {
+    let mut bytes = vec![0u8; 0x14];
+    bytes[0] = 0x03;
+    bytes[0x11] = 1; // big endian
+    bytes[0x12] = 0x01;
+    bytes[0x13] = XOR_VALUE;
+    bytes.extend(block(1, 0x87f2, &[0, 0]));
+
+    // INFO_Version: the application name, then the version
+    let mut version = vec![0];
+    version.extend(string("Pro Tools 7.4"));
+    version.extend_from_slice(&[0; 4]);
+    version.extend(u32(7));
+    bytes.extend(block(1, 0x0003, &version));
+
+    let mut sample_rate = vec![0, 0];
+    sample_rate.extend(u32(44100));
+    bytes.extend(block(1, 0x1028, &sample_rate));
+
+    // WAV_List_Full: the file names with their types and folder, then their lengths
+    let mut names = vec![0; 9];
+    for &(name, kind) in &[("Dialog.wav", b"EVAW"), ("Music.aif", b"FFIA")] {
+        names.extend(string(name));
+        names.extend_from_slice(kind);
+        names.extend_from_slice(&[0; 5]);
+    }
+    names.extend(string("Audio Files"));
+    names.extend_from_slice(&[0; 9]);
+    let mut wav_list = u32(2);
+    wav_list.extend(block(1, 0x103a, &names));
+    for &len in &[441000u64, 882000] {
+        let mut metadata = vec![0; 6];
+        metadata.extend(u64(len));
+        wav_list.extend(block(1, 0x1003, &block(1, 0x1001, &metadata)));
+    }
+    bytes.extend(block(2, 0x1004, &wav_list));
+
+    // AUDIO_Region_List_v5: name, three point and file of every region
+    let mut regions = u32(3);
+    for &(name, offset, len, start, wav) in &[
+        ("Dialog-01", 0, 220500, 44100, 0),
+        ("Music-01", 44100, 441000, 88200, 1),
+        ("Dialog-02", 220500, 110250, 441000, 0),
+    ] {
+        let mut info = string(name);
+        info.extend(three_point(offset, len, start));
+        let mut region = block(1, 0x1007, &info);
+        region.extend(u32(wav));
+        regions.extend(block(1, 0x1008, &region));
+    }
+    bytes.extend(block(1, 0x100b, &regions));
+
+    // AUDIO_Tracks: name and channels of every track
+    let mut tracks = vec![];
+    for (name, channels) in &[("DX", vec![0u16]), ("MX", vec![1, 2])] {
+        let mut track = string(name);
+        track.push(0);
+        track.extend(u32(channels.len() as u32));
+        for &channel in channels {
+            track.extend(u16(channel));
+        }
+        tracks.extend(block(6, 0x1014, &track));
+    }
+    bytes.extend(block(1, 0x1015, &tracks));
+
+    // AUDIO_Region_Track_Full_Map: the regions of every track channel
+    let mut map = vec![];
+    for (name, regions) in &[("DX", vec![0u32, 2]), ("MX", vec![1]), ("MX", vec![1])] {
+        let mut entries = string(name);
+        entries.extend(u32(regions.len() as u32));
+        for &region in regions {
+            let mut entry = vec![0, 0];
+            entry.extend(u32(region));
+            entry.extend_from_slice(&[0; 4]);
+            entries.extend(block(1, 0x100f, &block(1, 0x100e, &entry)));
+        }
+        map.extend(block(3, 0x1011, &entries));
+    }
+    bytes.extend(block(1, 0x1012, &map));
+    bytes.extend(block(1, 0x1055, &[0; 4]));
+
+    // Xor type 0x01 keys every byte after the header with its index
+    let delta = (0..=255u16).find(|i| (i * 53) as u8 == XOR_VALUE).unwrap();
+    for (i, byte) in bytes.iter_mut().enumerate().skip(0x14) {
+        *byte ^= ((i & 0xff) as u16 * delta) as u8;
+    }
+    bytes
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    std::fs::write("tests/LegacyRegionTest.ptf", session())?;
+    Ok(())
+}
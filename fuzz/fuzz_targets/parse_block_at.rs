@@ -0,0 +1,10 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+// The first byte picks the endianness and where the block starts, the rest is the decrypted session
+fuzz_target!(|data: &[u8]| {
+    if let Some((&first, unxored)) = data.split_first() {
+        let pos = (first >> 1) as usize;
+        let _ = ptsession::fuzz::parse_block_at(unxored.to_vec(), first & 1 == 1, pos);
+    }
+});
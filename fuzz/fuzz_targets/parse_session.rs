@@ -0,0 +1,9 @@
+#![no_main]
+use libfuzzer_sys::fuzz_target;
+
+use ptsession::PtSession;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = PtSession::from_bytes(data);
+    let _ = PtSession::from_bytes_lenient(data);
+});
@@ -19,13 +19,19 @@ macro_rules! back_to_enum {
                 }
             }
         }
+
+        impl From<$name> for u16 {
+            fn from(v: $name) -> u16 {
+                v as u16
+            }
+        }
     }
 }
 
 back_to_enum! {
-    #[allow(non_camel_case_types)]
+    #[allow(non_camel_case_types, clippy::upper_case_acronyms)]
     #[repr(u16)]
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum PTCD {
         DUMMY = 0x0000,
         INFO_Version = 0x0003,
@@ -36,6 +42,7 @@ back_to_enum! {
         REGION_Name_Number = 0x1007,
         AUDIO_Region_Name_Number_v5 = 0x1008,
         AUDIO_Region_List_v5 = 0x100b,
+        AUDIO_Region_Track_SubEntry = 0x100e,
         AUDIO_Region_Track_Entry = 0x100f,
         AUDIO_Region_Track_Map_Entries = 0x1011,
         AUDIO_Region_Track_Full_Map = 0x1012,
@@ -49,6 +56,7 @@ back_to_enum! {
         WAV_Names = 0x103a,
         AUDIO_Region_Track_SubEntry_v8 = 0x104f,
         AUDIO_Region_Track_Entry_v8 = 0x1050,
+        AUDIO_Region_Track_Map_Entries_v8 = 0x1052,
         AUDIO_Region_Track_Full_Map_v8 = 0x1054,
         MIDI_Region_Track_Entry = 0x1056,
         MIDI_Region_Track_Map_Entries = 0x1057,
@@ -58,11 +66,19 @@ back_to_enum! {
         MIDI_Regions_Map = 0x2002,
         INFO_Path_of_Session = 0x2067,
         Snaps_Block = 0x2511,
-        MIDI_Track_Full_List = 0x2519,
-        MIDI_Track_Name_Number = 0x251a,
+        TRACK_Full_List = 0x2519,
+        TRACK_Name_Number = 0x251a,
         COMPOUND_Region_element = 0x2523,
+        IO_Route_Port = 0x2600,
+        IO_Route_Ports = 0x2601,
         IO_Route = 0x2602,
         IO_Routing_Table = 0x2603,
+        TRACK_Output_List = 0x260d,
+        TRACK_Output = 0x260e,
+        OBJECT_Name_Id = 0x2619,
+        TRACK_IO = 0x261b,
+        TRACK_IO_Entry = 0x261c,
+        TRACK_IO_List = 0x2624,
         COMPOUND_Region_Group = 0x2628,
         AUDIO_Region_Name_Number_v10 = 0x2629,
         AUDIO_Region_List_v10 = 0x262a,
@@ -70,8 +86,18 @@ back_to_enum! {
         MIDI_Region_Name_Number_v10 = 0x2633,
         MIDI_Regions_Map_v10 = 0x2634,
         MARKER_List = 0x271a,
-        MARKER_Metadata = 0x2619,
         MARKER_List_Full = 0x2030,
         MARKER_List_Entry = 0x2077,
     }
 }
+
+// Names these content types had before it was known what they hold
+#[allow(non_upper_case_globals)]
+impl PTCD {
+    #[deprecated(note = "the list holds every track, use `TRACK_Full_List`")]
+    pub const MIDI_Track_Full_List: PTCD = PTCD::TRACK_Full_List;
+    #[deprecated(note = "the list holds every track, use `TRACK_Name_Number`")]
+    pub const MIDI_Track_Name_Number: PTCD = PTCD::TRACK_Name_Number;
+    #[deprecated(note = "names tracks and rulers as well as markers, use `OBJECT_Name_Id`")]
+    pub const MARKER_Metadata: PTCD = PTCD::OBJECT_Name_Id;
+}
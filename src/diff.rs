@@ -0,0 +1,299 @@
+//! Differences between two versions of a session.
+
+use crate::session::{Marker, PtSession, Region, Track, Wav};
+
+use serde::{Serialize, Deserialize};
+
+use std::fmt;
+
+/// Everything that changed from one session to another, see [`PtSession::diff`].
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct SessionDiff {
+    pub tracks: Vec<TrackChange>,
+    pub regions: Vec<RegionChange>,
+    pub audio_files: Vec<FileChange>,
+    pub markers: Vec<MarkerChange>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum TrackChange {
+    Added { name: String },
+    Removed { name: String },
+    Renamed { from: String, to: String },
+}
+
+/// Where a region sits on a track and which part of its audio it plays.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Placement {
+    pub start_pos: u64,
+    pub sample_offset: u64,
+    pub len: usize,
+}
+
+/// A region on a track, which is named as in the newer session.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum RegionChange {
+    Added { track: String, name: String, at: Placement },
+    Removed { track: String, name: String, at: Placement },
+    /// `moved` when its audio plays at another time, `trimmed` when another part of it plays.
+    /// Trimming the start of a region does not move it.
+    Changed { track: String, name: String, from: Placement, to: Placement, moved: bool, trimmed: bool },
+}
+
+/// Audio files by name, changed when their length differs.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum FileChange {
+    Added(Wav),
+    Removed(Wav),
+    Changed { from: Wav, to: Wav },
+}
+
+/// Markers by memory location number.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum MarkerChange {
+    Added(Marker),
+    Removed(Marker),
+    /// `moved` when its position differs, `edited` when its name or comment does.
+    Changed { from: Marker, to: Marker, moved: bool, edited: bool },
+}
+
+impl SessionDiff {
+    pub fn is_empty(&self) -> bool {
+        self.tracks.is_empty() && self.regions.is_empty() && self.audio_files.is_empty() && self.markers.is_empty()
+    }
+}
+
+impl PtSession {
+    /// What changed from this session to `other`.
+    ///
+    /// Tracks are matched by name, a track that is gone is taken as renamed when a new
+    /// track has the same regions or takes its place. Sessions at different sample rates
+    /// are compared at the sample rate of this one.
+    pub fn diff(&self, other: &PtSession) -> SessionDiff {
+        let resampled;
+        let other = if other.session_sample_rate != self.session_sample_rate && self.session_sample_rate != 0 {
+            resampled = other.clone().resampled(self.session_sample_rate);
+            &resampled
+        } else {
+            other
+        };
+
+        let mut diff = SessionDiff::default();
+        let old_tracks: Vec<&Track> = self.audio_tracks.iter().chain(&self.midi_tracks).collect();
+        let new_tracks: Vec<&Track> = other.audio_tracks.iter().chain(&other.midi_tracks).collect();
+        for (old, new) in match_tracks(&old_tracks, &new_tracks) {
+            match (old, new) {
+                (Some(old), Some(new)) => {
+                    if old.name != new.name {
+                        diff.tracks.push(TrackChange::Renamed { from: old.name.clone(), to: new.name.clone() });
+                    }
+                    diff_regions(&mut diff.regions, &new.name, &old.flattened_regions(), &new.flattened_regions());
+                }
+                (Some(old), None) => diff.tracks.push(TrackChange::Removed { name: old.name.clone() }),
+                (None, Some(new)) => diff.tracks.push(TrackChange::Added { name: new.name.clone() }),
+                (None, None) => {}
+            }
+        }
+
+        for old in &self.audio_files {
+            match other.audio_files.iter().find(|new| new.file_name == old.file_name) {
+                Some(new) if new.len != old.len => {
+                    diff.audio_files.push(FileChange::Changed { from: old.clone(), to: new.clone() });
+                }
+                Some(_) => {}
+                None => diff.audio_files.push(FileChange::Removed(old.clone())),
+            }
+        }
+        for new in &other.audio_files {
+            if !self.audio_files.iter().any(|old| old.file_name == new.file_name) {
+                diff.audio_files.push(FileChange::Added(new.clone()));
+            }
+        }
+
+        for old in &self.markers {
+            match other.markers.iter().find(|new| new.index == old.index) {
+                Some(new) if new != old => {
+                    diff.markers.push(MarkerChange::Changed {
+                        from: old.clone(),
+                        to: new.clone(),
+                        moved: new.sample_offset != old.sample_offset,
+                        edited: new.name != old.name || new.comment != old.comment,
+                    });
+                }
+                Some(_) => {}
+                None => diff.markers.push(MarkerChange::Removed(old.clone())),
+            }
+        }
+        for new in &other.markers {
+            if !self.markers.iter().any(|old| old.index == new.index) {
+                diff.markers.push(MarkerChange::Added(new.clone()));
+            }
+        }
+
+        diff
+    }
+}
+
+// Pairs of old and new tracks, in the order of the new session with removed tracks last
+fn match_tracks<'a>(old: &[&'a Track], new: &[&'a Track]) -> Vec<(Option<&'a Track>, Option<&'a Track>)> {
+    let mut pairs: Vec<Option<usize>> = new
+        .iter()
+        .map(|n| old.iter().position(|o| o.name == n.name))
+        .collect();
+    let unmatched = |pairs: &[Option<usize>], o: usize| !pairs.contains(&Some(o)) && !new.iter().any(|n| n.name == old[o].name);
+
+    // Renamed tracks keep their regions, or their place among the tracks
+    for same_regions in [true, false] {
+        for n in 0..new.len() {
+            if pairs[n].is_some() {
+                continue;
+            }
+            let found = (0..old.len()).find(|&o| {
+                unmatched(&pairs, o) && if same_regions {
+                    !old[o].regions.is_empty() && old[o].flattened_regions() == new[n].flattened_regions()
+                } else {
+                    old[o].index == new[n].index && old[o].channels.len() == new[n].channels.len()
+                }
+            });
+            pairs[n] = found;
+        }
+    }
+
+    let removed: Vec<usize> = (0..old.len()).filter(|&o| !pairs.contains(&Some(o))).collect();
+    pairs
+        .iter()
+        .zip(new)
+        .map(|(o, n)| (o.map(|o| old[o]), Some(*n)))
+        .chain(removed.into_iter().map(|o| (Some(old[o]), None)))
+        .collect()
+}
+
+fn diff_regions(changes: &mut Vec<RegionChange>, track: &str, old: &[Region], new: &[Region]) {
+    let placement = |r: &Region| Placement { start_pos: r.start_pos, sample_offset: r.sample_offset, len: r.len };
+    let same = |a: &Region, b: &Region| a.name == b.name && placement(a) == placement(b);
+
+    // Unchanged regions first, so a region used twice on a track pairs up with the right copy
+    let (mut old, new): (Vec<&Region>, Vec<&Region>) = (
+        old.iter().filter(|o| !new.iter().any(|n| same(o, n))).collect(),
+        new.iter().filter(|n| !old.iter().any(|o| same(o, n))).collect(),
+    );
+
+    for n in new {
+        let to = placement(n);
+        match old.iter().position(|o| o.name == n.name) {
+            Some(i) => {
+                let from = placement(old.remove(i));
+                changes.push(RegionChange::Changed {
+                    track: track.to_string(),
+                    name: n.name.clone(),
+                    from,
+                    to,
+                    moved: from.start_pos as i128 - from.sample_offset as i128 != to.start_pos as i128 - to.sample_offset as i128,
+                    trimmed: from.sample_offset != to.sample_offset || from.len != to.len,
+                });
+            }
+            None => changes.push(RegionChange::Added { track: track.to_string(), name: n.name.clone(), at: to }),
+        }
+    }
+    for o in old {
+        changes.push(RegionChange::Removed { track: track.to_string(), name: o.name.clone(), at: placement(o) });
+    }
+}
+
+impl fmt::Display for Placement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@ {}, {} +{}", self.start_pos, self.sample_offset, self.len)
+    }
+}
+
+impl fmt::Display for SessionDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.tracks {
+            match change {
+                TrackChange::Added { name } => writeln!(f, "+ track `{}`", name)?,
+                TrackChange::Removed { name } => writeln!(f, "- track `{}`", name)?,
+                TrackChange::Renamed { from, to } => writeln!(f, "~ track `{}` renamed `{}`", from, to)?,
+            }
+        }
+        for change in &self.regions {
+            match change {
+                RegionChange::Added { track, name, at } => writeln!(f, "+ region `{}` on `{}` {}", name, track, at)?,
+                RegionChange::Removed { track, name, at } => writeln!(f, "- region `{}` on `{}` {}", name, track, at)?,
+                RegionChange::Changed { track, name, from, to, moved, trimmed } => {
+                    let what = match (moved, trimmed) {
+                        (true, true) => "moved, trimmed",
+                        (true, false) => "moved",
+                        _ => "trimmed",
+                    };
+                    writeln!(f, "~ region `{}` on `{}` {} {} -> {}", name, track, what, from, to)?;
+                }
+            }
+        }
+        for change in &self.audio_files {
+            match change {
+                FileChange::Added(wav) => writeln!(f, "+ file `{}`, {}", wav.file_name, wav.len)?,
+                FileChange::Removed(wav) => writeln!(f, "- file `{}`, {}", wav.file_name, wav.len)?,
+                FileChange::Changed { from, to } => {
+                    writeln!(f, "~ file `{}`, {} -> {}", to.file_name, from.len, to.len)?;
+                }
+            }
+        }
+        for change in &self.markers {
+            match change {
+                MarkerChange::Added(m) => writeln!(f, "+ marker {} `{}` @ {}", m.index, m.name, m.sample_offset)?,
+                MarkerChange::Removed(m) => writeln!(f, "- marker {} `{}` @ {}", m.index, m.name, m.sample_offset)?,
+                MarkerChange::Changed { from, to, .. } => writeln!(
+                    f,
+                    "~ marker {} `{}` @ {} -> `{}` @ {}",
+                    to.index, from.name, from.sample_offset, to.name, to.sample_offset
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        let old = PtSession::open("tests/RegionTest.ptx").unwrap();
+        assert!(old.diff(&old).is_empty());
+
+        let mut new = old.clone();
+        new.audio_tracks[0].name = "Dialog".to_string();
+        new.audio_tracks.remove(1);
+        new.audio_tracks[2].regions[0].start_pos += 100;
+        let region = &mut new.audio_tracks[2].regions[0];
+        region.sample_offset += 10;
+        region.start_pos += 10;
+        region.len -= 10;
+        new.audio_files[0].len += 1;
+        new.markers.push(Marker { name: "Scene 1".to_string(), index: 1, ..Default::default() });
+
+        let diff = old.diff(&new);
+        assert_eq!(diff.tracks, vec![
+            TrackChange::Renamed { from: "Track_Name".to_string(), to: "Dialog".to_string() },
+            TrackChange::Removed { name: "Audio 1".to_string() },
+        ]);
+        assert_eq!(diff.regions, vec![RegionChange::Changed {
+            track: "Audio 3".to_string(),
+            name: "region_name_region-03".to_string(),
+            from: Placement { start_pos: 0, sample_offset: 950000, len: 1290000 },
+            to: Placement { start_pos: 110, sample_offset: 950010, len: 1289990 },
+            moved: true,
+            trimmed: true,
+        }]);
+        assert!(matches!(diff.audio_files[..], [FileChange::Changed { .. }]));
+        assert!(matches!(diff.markers[..], [MarkerChange::Added(_)]));
+        assert_eq!(old.diff(&new.clone().resampled(88200)), diff);
+    }
+}
@@ -0,0 +1,140 @@
+//! Decoding the names stored in a session.
+//!
+//! Pro Tools 10 and later store names as UTF-8. Older sessions store them in the
+//! legacy encoding of the system they were saved on, Mac Roman or Windows-1252.
+
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum Encoding {
+    /// Invalid sequences are replaced with U+FFFD
+    Utf8,
+    MacRoman,
+    Windows1252,
+}
+
+impl Default for Encoding {
+    fn default() -> Self {
+        Encoding::Utf8
+    }
+}
+
+/// The bytes of a string that were not UTF-8, and how they were decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct RawString {
+    pub bytes: Vec<u8>,
+    pub encoding: Encoding,
+}
+
+impl Encoding {
+    /// The legacy encoding `bytes` most likely are in: the one in which more of
+    /// them decode to letters of the same case as the letter before them, Mac Roman
+    /// when that is a tie.
+    pub fn detect(bytes: &[u8]) -> Encoding {
+        let letters = |encoding: Encoding| {
+            let mut prev = 0u8;
+            bytes
+                .iter()
+                .filter(|&&b| {
+                    let c = encoding.decode_byte(b);
+                    let after = std::mem::replace(&mut prev, b);
+                    b >= 0x80 && c.is_alphabetic() && match after {
+                        b'a'..=b'z' => c.is_lowercase(),
+                        b'A'..=b'Z' => c.is_uppercase(),
+                        _ => true,
+                    }
+                })
+                .count()
+        };
+        if letters(Encoding::Windows1252) > letters(Encoding::MacRoman) {
+            Encoding::Windows1252
+        } else {
+            Encoding::MacRoman
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match self {
+            Encoding::Utf8 => String::from_utf8_lossy(bytes).into_owned(),
+            _ => bytes.iter().map(|&b| self.decode_byte(b)).collect(),
+        }
+    }
+
+    // Character of a byte in a single byte encoding
+    fn decode_byte(self, b: u8) -> char {
+        match (self, b) {
+            (_, 0x00..=0x7f) => b as char,
+            (Encoding::MacRoman, _) => MAC_ROMAN[b as usize - 0x80],
+            (_, 0x80..=0x9f) => WINDOWS_1252[b as usize - 0x80],
+            // Latin-1
+            _ => b as char,
+        }
+    }
+}
+
+impl RawString {
+    pub fn decode(&self) -> String {
+        self.encoding.decode(&self.bytes)
+    }
+}
+
+/// Decodes a name read from a session of `version`, the raw bytes are kept when
+/// they were not UTF-8: non-ASCII names of older sessions, and invalid UTF-8.
+pub(crate) fn decode_name(bytes: &[u8], version: Option<u8>) -> (String, Option<RawString>) {
+    let encoding = match version {
+        // Legacy bytes can happen to be valid UTF-8 as well
+        Some(version) if version < 10 && !bytes.is_ascii() => Encoding::detect(bytes),
+        _ => match std::str::from_utf8(bytes) {
+            Ok(name) => return (name.to_string(), None),
+            Err(_) => Encoding::Utf8,
+        },
+    };
+    let raw = RawString { bytes: bytes.to_vec(), encoding };
+    (raw.decode(), Some(raw))
+}
+
+const MAC_ROMAN: [char; 128] = [
+    'Ä', 'Å', 'Ç', 'É', 'Ñ', 'Ö', 'Ü', 'á', 'à', 'â', 'ä', 'ã', 'å', 'ç', 'é', 'è',
+    'ê', 'ë', 'í', 'ì', 'î', 'ï', 'ñ', 'ó', 'ò', 'ô', 'ö', 'õ', 'ú', 'ù', 'û', 'ü',
+    '†', '°', '¢', '£', '§', '•', '¶', 'ß', '®', '©', '™', '´', '¨', '≠', 'Æ', 'Ø',
+    '∞', '±', '≤', '≥', '¥', 'µ', '∂', '∑', '∏', 'π', '∫', 'ª', 'º', 'Ω', 'æ', 'ø',
+    '¿', '¡', '¬', '√', 'ƒ', '≈', '∆', '«', '»', '…', '\u{a0}', 'À', 'Ã', 'Õ', 'Œ', 'œ',
+    '–', '—', '“', '”', '‘', '’', '÷', '◊', 'ÿ', 'Ÿ', '⁄', '€', '‹', '›', 'ﬁ', 'ﬂ',
+    '‡', '·', '‚', '„', '‰', 'Â', 'Ê', 'Á', 'Ë', 'È', 'Í', 'Î', 'Ï', 'Ì', 'Ó', 'Ô',
+    '\u{f8ff}', 'Ò', 'Ú', 'Û', 'Ù', 'ı', 'ˆ', '˜', '¯', '˘', '˙', '˚', '¸', '˝', '˛', 'ˇ',
+];
+
+// 0x80 - 0x9f, the rest of the upper half is Latin-1. Unassigned bytes map to the C1 controls.
+const WINDOWS_1252: [char; 32] = [
+    '€', '\u{81}', '‚', 'ƒ', '„', '…', '†', '‡', 'ˆ', '‰', 'Š', '‹', 'Œ', '\u{8d}', 'Ž', '\u{8f}',
+    '\u{90}', '‘', '’', '“', '”', '•', '–', '—', '˜', '™', 'š', '›', 'œ', '\u{9d}', 'ž', 'Ÿ',
+];
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn decode() {
+        assert_eq!(decode_name(b"Cafe", Some(7)), ("Cafe".to_string(), None));
+        assert_eq!(decode_name("Café".as_bytes(), Some(12)), ("Café".to_string(), None));
+        // Older sessions are not UTF-8, even where the bytes happen to be
+        let (name, raw) = decode_name("Café".as_bytes(), Some(7));
+        assert_eq!(name, "Caf√©");
+        assert_eq!(raw.unwrap().encoding, Encoding::MacRoman);
+
+        // "Café Crème" as saved on a Mac and on Windows
+        let (name, raw) = decode_name(b"Caf\x8e Cr\x8fme", Some(7));
+        assert_eq!(name, "Café Crème");
+        assert_eq!(raw.unwrap().encoding, Encoding::MacRoman);
+        let (name, raw) = decode_name(b"Caf\xe9 Cr\xe8me", Some(7));
+        assert_eq!(name, "Café Crème");
+        assert_eq!(raw.unwrap(), RawString { bytes: b"Caf\xe9 Cr\xe8me".to_vec(), encoding: Encoding::Windows1252 });
+
+        let (name, raw) = decode_name(b"Caf\xe9", Some(12));
+        assert_eq!(name, "Caf\u{fffd}");
+        assert_eq!(raw.unwrap().encoding, Encoding::Utf8);
+    }
+}
@@ -1,3 +1,6 @@
+use crate::content_description::PTCD;
+
+use std::convert::TryFrom;
 use std::error::Error;
 use std::io::Error as IoError;
 use std::fmt;
@@ -8,8 +11,9 @@ pub enum PtError {
     BitCode,
     Endianness,
     Version(String),
-    Parse,
+    Parse(ParseError),
     Io(IoError),
+    Save(IoError),
 }
 
 impl fmt::Display for PtError {
@@ -20,10 +24,168 @@ impl fmt::Display for PtError {
             BitCode => write!(f, "Could not verify BitCode. Not a Pro Tools file."),
             Endianness => write!(f, "Could not parse the endianness. Expected 0 or 1"),
             Version(err) => write!(f, "Pro Tools version not supported. Only support 5 - 12. {}", err),
-            Parse => write!(f, "Error parsing blocks"),
+            Parse(err) => write!(f, "{}", err),
             Io(err) => write!(f, "IO Error: {}", err),
+            Save(err) => write!(f, "Could not save the session: {}", err),
+        }
+    }
+}
+
+impl Error for PtError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        use PtError::*;
+        match self {
+            Decrypt(err) | Io(err) | Save(err) => Some(err),
+            Parse(err) => err.source(),
+            _ => None,
+        }
+    }
+}
+
+impl From<ParseError> for PtError {
+    fn from(err: ParseError) -> Self {
+        PtError::Parse(err)
+    }
+}
+
+/// Part of a session that was being parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Section {
+    Blocks,
+    Header,
+    AudioFiles,
+    AudioRegions,
+    AudioTracks,
+    Midi,
+    Io,
+    Markers,
+    Plugins,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use Section::*;
+        let name = match self {
+            Blocks => "blocks",
+            Header => "header",
+            AudioFiles => "audio files",
+            AudioRegions => "audio regions",
+            AudioTracks => "audio tracks",
+            Midi => "MIDI",
+            Io => "I/O",
+            Markers => "markers",
+            Plugins => "plugins",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// A session that could not be parsed, and where.
+#[derive(Debug)]
+pub struct ParseError {
+    pub section: Section,
+    /// Byte offset into the decrypted file, or into the text being parsed
+    pub offset: Option<usize>,
+    /// Content types of the blocks around the offset, outermost first
+    pub block_path: Vec<u16>,
+    pub message: String,
+    source: Option<IoError>,
+}
+
+impl ParseError {
+    pub fn new<S: Into<String>>(section: Section, message: S) -> Self {
+        ParseError {
+            section,
+            offset: None,
+            block_path: vec![],
+            message: message.into(),
+            source: None,
+        }
+    }
+
+    /// An error reading `section`, caused by `err`.
+    pub fn io(section: Section, err: IoError) -> Self {
+        ParseError {
+            source: Some(err),
+            ..ParseError::new(section, "")
         }
     }
+
+    pub fn at(self, offset: usize) -> Self {
+        ParseError { offset: Some(offset), ..self }
+    }
+
+    pub fn in_blocks(self, block_path: Vec<u16>) -> Self {
+        ParseError { block_path, ..self }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Error parsing {}", self.section)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        write_block_path(f, &self.block_path)?;
+        match &self.source {
+            Some(err) => write!(f, ": {}", err),
+            None => write!(f, ": {}", self.message),
+        }
+    }
+}
+
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source.as_ref().map(|err| err as &(dyn Error + 'static))
+    }
 }
 
-impl Error for PtError {}
+/// A problem lenient parsing worked around, see `PtSessionParser::parse_session_lenient`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub section: Section,
+    /// Byte offset into the decrypted file
+    pub offset: Option<usize>,
+    /// Content types of the blocks around the offset, outermost first
+    pub block_path: Vec<u16>,
+    pub message: String,
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(err: ParseError) -> Self {
+        Diagnostic {
+            message: match &err.source {
+                Some(source) => source.to_string(),
+                None => err.message,
+            },
+            section: err.section,
+            offset: err.offset,
+            block_path: err.block_path,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.section)?;
+        if let Some(offset) = self.offset {
+            write!(f, " at byte {}", offset)?;
+        }
+        write_block_path(f, &self.block_path)?;
+        write!(f, ": {}", self.message)
+    }
+}
+
+fn write_block_path(f: &mut fmt::Formatter<'_>, block_path: &[u16]) -> fmt::Result {
+    if block_path.is_empty() {
+        return Ok(());
+    }
+    let names: Vec<String> = block_path
+        .iter()
+        .map(|&content_type| match PTCD::try_from(content_type) {
+            Ok(ptcd) => format!("{:?}", ptcd),
+            Err(_) => format!("{:#06x}", content_type),
+        })
+        .collect();
+    write!(f, " in {}", names.join(" > "))
+}
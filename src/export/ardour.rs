@@ -0,0 +1,336 @@
+//! Ardour session documents.
+
+use crate::session::{PtSession, Region, Track, Wav};
+
+use std::io::{self, Write};
+
+/// Session format version written, the Ardour 6 format: tracks refer to their
+/// playlist by id and positions are samples. Newer Ardour versions upgrade it
+/// when the session is opened.
+const SESSION_VERSION: u32 = 6000;
+
+/// Writes the session as an Ardour session document (`.ardour`) named `name`.
+///
+/// `audio_dir` is added to the session's audio search path so Ardour finds the
+/// Pro Tools audio files without copying them. Multichannel tracks become one
+/// Ardour track whose regions read one source per channel. Ardour locations have
+/// no comment, so marker comments are appended to the marker name.
+pub fn write<W: Write>(out: &mut W, session: &PtSession, name: &str, audio_dir: &str) -> io::Result<()> {
+    Writer::new(session).write(out, name, audio_dir)
+}
+
+/// The Ardour session document as a string, see [`write`].
+pub fn to_string(session: &PtSession, name: &str, audio_dir: &str) -> String {
+    super::to_string(|out| write(out, session, name, audio_dir))
+}
+
+struct Source {
+    id: u64,
+    wav: Wav,
+}
+
+// A region of every channel of a track, placed at the same position
+struct TrackRegion {
+    id: u64,
+    region: Region,
+    sources: Vec<u64>,
+}
+
+struct ArdourTrack<'a> {
+    track: &'a Track,
+    route_id: u64,
+    playlist_id: u64,
+    regions: Vec<TrackRegion>,
+}
+
+struct Writer<'a> {
+    session: &'a PtSession,
+    next_id: u64,
+    sources: Vec<Source>,
+}
+
+impl<'a> Writer<'a> {
+    fn new(session: &'a PtSession) -> Self {
+        Writer { session, next_id: 1, sources: vec![] }
+    }
+
+    fn id(&mut self) -> u64 {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    fn source_id(&mut self, wav: &Wav) -> u64 {
+        if let Some(source) = self.sources.iter().find(|s| s.wav.file_name == wav.file_name) {
+            return source.id;
+        }
+
+        // The session's file list holds the whole file length, regions only their own
+        let wav = self.session.audio_files
+            .iter()
+            .find(|w| w.file_name == wav.file_name)
+            .unwrap_or(wav)
+            .clone();
+        let id = self.id();
+        self.sources.push(Source { id, wav });
+        id
+    }
+
+    fn track(&mut self, track: &'a Track) -> ArdourTrack<'a> {
+        let route_id = self.id();
+        let playlist_id = self.id();
+        let channels: Vec<Vec<Region>> = match track.channels.len() {
+            0 => vec![track.flattened_regions()],
+            _ => track.split_channels().iter().map(Track::flattened_regions).collect(),
+        };
+
+        let mut regions = vec![];
+        for (i, region) in channels[0].iter().enumerate() {
+            let sources: Option<Vec<u64>> = channels
+                .iter()
+                .map(|regions| regions.get(i).and_then(|r| r.wav.as_ref()).map(|wav| self.source_id(wav)))
+                .collect();
+            if let Some(sources) = sources {
+                regions.push(TrackRegion { id: self.id(), region: region.clone(), sources });
+            }
+        }
+
+        ArdourTrack { track, route_id, playlist_id, regions }
+    }
+
+    fn write<W: Write>(mut self, out: &mut W, name: &str, audio_dir: &str) -> io::Result<()> {
+        let tracks: Vec<ArdourTrack> = self.session.audio_tracks.iter().map(|t| self.track(t)).collect();
+        let master_id = self.id();
+        let whole_file_ids: Vec<u64> = (0..self.sources.len()).map(|_| self.id()).collect();
+        let session_id = self.id();
+        let marker_ids: Vec<u64> = self.session.markers.iter().map(|_| self.id()).collect();
+        let rate = self.session.session_sample_rate;
+
+        writeln!(out, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+        writeln!(
+            out,
+            r#"<Session version="{}" name="{}" sample-rate="{}" id-counter="{}">"#,
+            SESSION_VERSION, escape(name), rate, self.next_id
+        )?;
+        writeln!(out, "  <Config>")?;
+        writeln!(out, r#"    <Option name="audio-search-path" value="{}"/>"#, escape(audio_dir))?;
+        writeln!(out, "  </Config>")?;
+
+        writeln!(out, "  <Sources>")?;
+        for source in &self.sources {
+            writeln!(
+                out,
+                r#"    <Source name="{}" type="audio" flags="" id="{}" captured-for="" channel="0" origin="" gain="1"/>"#,
+                escape(&source.wav.file_name), source.id
+            )?;
+        }
+        writeln!(out, "  </Sources>")?;
+
+        // Every source needs a whole file region
+        writeln!(out, "  <Regions>")?;
+        for (source, id) in self.sources.iter().zip(&whole_file_ids) {
+            let whole_file = Region {
+                name: source.wav.file_name.clone(),
+                len: source.wav.len,
+                ..Default::default()
+            };
+            write_region(out, "    ", *id, &whole_file, &[source.id], true)?;
+        }
+        writeln!(out, "  </Regions>")?;
+
+        let end = tracks
+            .iter()
+            .flat_map(|t| t.regions.iter())
+            .map(|r| r.region.start_pos + r.region.len as u64)
+            .max()
+            .unwrap_or(0);
+        writeln!(out, "  <Locations>")?;
+        writeln!(
+            out,
+            r#"    <Location id="{}" name="session" start="0" end="{}" flags="IsSessionRange" locked="0" position-lock-style="AudioTime"/>"#,
+            session_id, end
+        )?;
+        for (marker, id) in self.session.markers.iter().zip(marker_ids) {
+            let name = if marker.comment.is_empty() {
+                marker.name.clone()
+            } else {
+                format!("{} - {}", marker.name, marker.comment)
+            };
+            writeln!(
+                out,
+                r#"    <Location id="{}" name="{}" start="{pos}" end="{pos}" flags="IsMark" locked="0" position-lock-style="AudioTime"/>"#,
+                id, escape(&name), pos = marker.sample_offset
+            )?;
+        }
+        writeln!(out, "  </Locations>")?;
+
+        writeln!(out, "  <Routes>")?;
+        write_route(out, master_id, "Master", 2, None, None)?;
+        for t in &tracks {
+            let num_channels = t.track.channels.len().max(1);
+            write_route(out, t.route_id, &t.track.name, num_channels, Some(t.playlist_id), Some("Master"))?;
+        }
+        writeln!(out, "  </Routes>")?;
+
+        writeln!(out, "  <Playlists>")?;
+        for t in &tracks {
+            writeln!(
+                out,
+                r#"    <Playlist id="{}" name="{}" type="audio" orig-track-id="{}" shared-with-ids="" frozen="0" combine-ops="0">"#,
+                t.playlist_id, escape(&t.track.name), t.route_id
+            )?;
+            for r in &t.regions {
+                write_region(out, "      ", r.id, &r.region, &r.sources, false)?;
+            }
+            writeln!(out, "    </Playlist>")?;
+        }
+        writeln!(out, "  </Playlists>")?;
+        writeln!(out, "  <UnusedPlaylists/>")?;
+
+        writeln!(out, "</Session>")
+    }
+}
+
+fn write_region<W: Write>(
+    out: &mut W,
+    indent: &str,
+    id: u64,
+    region: &Region,
+    sources: &[u64],
+    whole_file: bool,
+) -> io::Result<()> {
+    write!(
+        out,
+        r#"{}<Region name="{}" id="{}" type="audio" position="{}" length="{}" start="{}" sync-position="0" ancestral-start="0" ancestral-length="0" stretch="1" shift="1" layer="0" whole-file="{}" muted="0" opaque="1" locked="0" positional-lock-style="AudioTime" channels="{}""#,
+        indent,
+        escape(&region.name),
+        id,
+        region.start_pos,
+        region.len,
+        region.sample_offset,
+        whole_file as u8,
+        sources.len()
+    )?;
+    for (i, source) in sources.iter().enumerate() {
+        write!(out, r#" source-{i}="{s}" master-source-{i}="{s}""#, i = i, s = source)?;
+    }
+    writeln!(out, "/>")
+}
+
+fn write_route<W: Write>(
+    out: &mut W,
+    id: u64,
+    name: &str,
+    num_channels: usize,
+    playlist_id: Option<u64>,
+    output: Option<&str>,
+) -> io::Result<()> {
+    let name = escape(name);
+    write!(out, r#"    <Route version="{}" id="{}" name="{}" default-type="audio" active="1""#, SESSION_VERSION, id, name)?;
+    match playlist_id {
+        Some(playlist_id) => write!(out, r#" audio-playlist="{}" mode="Normal""#, playlist_id)?,
+        None => write!(out, r#" flags="MasterOut""#)?,
+    }
+    writeln!(out, ">")?;
+
+    for (direction, port) in &[("Input", "audio_in"), ("Output", "audio_out")] {
+        writeln!(out, r#"      <IO name="{}" direction="{}" default-type="audio">"#, name, direction)?;
+        // Track outputs are stereo, feeding the master bus
+        let num_ports = if *direction == "Output" { 2 } else { num_channels };
+        for i in 1..=num_ports {
+            match output.filter(|_| *direction == "Output") {
+                Some(other) => writeln!(
+                    out,
+                    r#"        <Port type="audio" name="{}/{} {i}"><Connection other="{}/audio_in {i}"/></Port>"#,
+                    name, port, other, i = i
+                )?,
+                None => writeln!(out, r#"        <Port type="audio" name="{}/{} {}"/>"#, name, port, i)?,
+            }
+        }
+        writeln!(out, "      </IO>")?;
+    }
+
+    writeln!(out, "    </Route>")
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{export::test::{channel, region}, session::Marker};
+
+    #[test]
+    fn session() {
+        let channel = |index, name, file_name| channel(index, vec![region(name, file_name, 48000, 1000, 24000)]);
+        let session = PtSession {
+            session_sample_rate: 48000,
+            audio_files: vec![Wav { file_name: "Score.L.wav".to_string(), len: 480000, ..Default::default() }],
+            audio_tracks: vec![Track {
+                name: "M&E".to_string(),
+                channels: vec![channel(0, "Score.L", "Score.L.wav"), channel(1, "Score.R", "Score.R.wav")],
+                ..Default::default()
+            }],
+            markers: vec![
+                Marker { name: "Reel 1".to_string(), sample_offset: 96000, ..Default::default() },
+                Marker { name: "Sync".to_string(), comment: "Check".to_string(), sample_offset: 192000, ..Default::default() },
+            ],
+            ..Default::default()
+        };
+
+        // Ardour 6 format, without the diskstreams of older versions
+        assert_eq!(to_string(&session, "Reel 1", "/mnt/Audio Files"), r#"<?xml version="1.0" encoding="UTF-8"?>
+<Session version="6000" name="Reel 1" sample-rate="48000" id-counter="12">
+  <Config>
+    <Option name="audio-search-path" value="/mnt/Audio Files"/>
+  </Config>
+  <Sources>
+    <Source name="Score.L.wav" type="audio" flags="" id="3" captured-for="" channel="0" origin="" gain="1"/>
+    <Source name="Score.R.wav" type="audio" flags="" id="4" captured-for="" channel="0" origin="" gain="1"/>
+  </Sources>
+  <Regions>
+    <Region name="Score.L.wav" id="7" type="audio" position="0" length="480000" start="0" sync-position="0" ancestral-start="0" ancestral-length="0" stretch="1" shift="1" layer="0" whole-file="1" muted="0" opaque="1" locked="0" positional-lock-style="AudioTime" channels="1" source-0="3" master-source-0="3"/>
+    <Region name="Score.R.wav" id="8" type="audio" position="0" length="0" start="0" sync-position="0" ancestral-start="0" ancestral-length="0" stretch="1" shift="1" layer="0" whole-file="1" muted="0" opaque="1" locked="0" positional-lock-style="AudioTime" channels="1" source-0="4" master-source-0="4"/>
+  </Regions>
+  <Locations>
+    <Location id="9" name="session" start="0" end="72000" flags="IsSessionRange" locked="0" position-lock-style="AudioTime"/>
+    <Location id="10" name="Reel 1" start="96000" end="96000" flags="IsMark" locked="0" position-lock-style="AudioTime"/>
+    <Location id="11" name="Sync - Check" start="192000" end="192000" flags="IsMark" locked="0" position-lock-style="AudioTime"/>
+  </Locations>
+  <Routes>
+    <Route version="6000" id="6" name="Master" default-type="audio" active="1" flags="MasterOut">
+      <IO name="Master" direction="Input" default-type="audio">
+        <Port type="audio" name="Master/audio_in 1"/>
+        <Port type="audio" name="Master/audio_in 2"/>
+      </IO>
+      <IO name="Master" direction="Output" default-type="audio">
+        <Port type="audio" name="Master/audio_out 1"/>
+        <Port type="audio" name="Master/audio_out 2"/>
+      </IO>
+    </Route>
+    <Route version="6000" id="1" name="M&amp;E" default-type="audio" active="1" audio-playlist="2" mode="Normal">
+      <IO name="M&amp;E" direction="Input" default-type="audio">
+        <Port type="audio" name="M&amp;E/audio_in 1"/>
+        <Port type="audio" name="M&amp;E/audio_in 2"/>
+      </IO>
+      <IO name="M&amp;E" direction="Output" default-type="audio">
+        <Port type="audio" name="M&amp;E/audio_out 1"><Connection other="Master/audio_in 1"/></Port>
+        <Port type="audio" name="M&amp;E/audio_out 2"><Connection other="Master/audio_in 2"/></Port>
+      </IO>
+    </Route>
+  </Routes>
+  <Playlists>
+    <Playlist id="2" name="M&amp;E" type="audio" orig-track-id="1" shared-with-ids="" frozen="0" combine-ops="0">
+      <Region name="Score.L" id="5" type="audio" position="48000" length="24000" start="1000" sync-position="0" ancestral-start="0" ancestral-length="0" stretch="1" shift="1" layer="0" whole-file="0" muted="0" opaque="1" locked="0" positional-lock-style="AudioTime" channels="2" source-0="3" master-source-0="3" source-1="4" master-source-1="4"/>
+    </Playlist>
+  </Playlists>
+  <UnusedPlaylists/>
+</Session>
+"#);
+    }
+}
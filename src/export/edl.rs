@@ -0,0 +1,217 @@
+//! CMX3600 edit decision lists.
+
+use crate::session::{PtSession, Region, Track, Wav};
+
+use std::io::{self, Write};
+
+/// Reel names are limited to eight characters
+const REEL_LEN: usize = 8;
+
+/// Audio channels an event can be on, A to A4
+pub const MAX_CHANNELS: usize = 4;
+
+/// Events are numbered with three digits
+pub const MAX_EVENTS: usize = 999;
+
+/// Writes a CMX3600 EDL of `track`, one event per region on each of its channels.
+///
+/// Record times are session timecode, source times count from the start of the audio file.
+/// Reels are named after the audio files, and made unique within the EDL. Regions without
+/// an audio file are skipped. Fails with `InvalidInput` when the timecode rate or start of
+/// the session is not known, the track has more than [`MAX_CHANNELS`] channels or more
+/// than [`MAX_EVENTS`] events, which have to be split over several EDLs.
+pub fn write<W: Write>(out: &mut W, session: &PtSession, track: &Track) -> io::Result<()> {
+    let record = session.timeline()?;
+    let source = record.with_start(0);
+    let channels = track.channel_regions();
+    if channels.len() > MAX_CHANNELS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Track `{}` has {} channels, an EDL has {}", track.name, channels.len(), MAX_CHANNELS),
+        ));
+    }
+
+    // In order of the record time, channels of the same time in order
+    let mut events: Vec<(usize, &Region, &Wav)> = channels
+        .iter()
+        .enumerate()
+        .flat_map(|(channel, regions)| regions.iter().map(move |region| (channel, region)))
+        .filter_map(|(channel, region)| Some((channel, region, region.wav.as_ref()?)))
+        .collect();
+    events.sort_by_key(|(channel, region, _)| (region.start_pos, *channel));
+    if events.len() > MAX_EVENTS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Track `{}` has {} events, an EDL has {}", track.name, events.len(), MAX_EVENTS),
+        ));
+    }
+    let reels = reels(events.iter().map(|(_, _, wav)| wav.file_name.as_str()));
+
+    writeln!(out, "TITLE: {}", track.name)?;
+    if record.timecode_rate().is_drop_frame() {
+        writeln!(out, "FCM: DROP FRAME")?;
+    } else {
+        writeln!(out, "FCM: NON-DROP FRAME")?;
+    }
+    writeln!(out)?;
+
+    for (event, (channel, region, wav)) in events.into_iter().enumerate() {
+        let len = region.len as u64;
+        let reel = reels.iter().find(|(file_name, _)| *file_name == wav.file_name).map_or("AX", |(_, reel)| reel);
+        writeln!(
+            out,
+            "{:03}  {:<8} {:<5} C        {} {} {} {}",
+            event + 1,
+            reel,
+            channel_code(channel),
+            source.timecode(region.sample_offset),
+            source.timecode(region.sample_offset + len),
+            record.timecode(region.start_pos),
+            record.timecode(region.start_pos + len),
+        )?;
+        writeln!(out, "* FROM CLIP NAME: {}", region.name)?;
+        writeln!(out, "* SOURCE FILE: {}", wav.file_name)?;
+        writeln!(out)?;
+    }
+
+    Ok(())
+}
+
+/// EDL of `track` as a string, see [`write`].
+pub fn to_string(session: &PtSession, track: &Track) -> io::Result<String> {
+    super::try_to_string(|out| write(out, session, track))
+}
+
+// A for the first channel, then A2 to A4
+fn channel_code(channel: usize) -> String {
+    match channel {
+        0 => "A".to_string(),
+        _ => format!("A{}", channel + 1),
+    }
+}
+
+// Reel of each audio file, in order of first use. Files whose reel names collide get a
+// number in the last characters of the name.
+fn reels<'a>(file_names: impl Iterator<Item = &'a str>) -> Vec<(&'a str, String)> {
+    let mut reels: Vec<(&str, String)> = vec![];
+    for file_name in file_names {
+        if reels.iter().any(|(used, _)| *used == file_name) {
+            continue;
+        }
+        let name = reel(file_name);
+        let mut unique = name.clone();
+        let mut number = 1;
+        while reels.iter().any(|(_, reel)| *reel == unique) {
+            number += 1;
+            let suffix = number.to_string();
+            unique = name.chars().take(REEL_LEN - suffix.len()).collect::<String>() + &suffix;
+        }
+        reels.push((file_name, unique));
+    }
+    reels
+}
+
+/// Reel name derived from the audio file name, without extension and punctuation
+fn reel(file_name: &str) -> String {
+    let stem = file_name.rsplit_once('.').map_or(file_name, |(stem, _)| stem);
+    let reel: String = stem
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || *c == '_')
+        .take(REEL_LEN)
+        .collect::<String>()
+        .to_ascii_uppercase();
+
+    if reel.is_empty() {
+        "AX".to_string()
+    } else {
+        reel
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{export::test::{channel, region}, timecode::TimecodeRate};
+
+    #[test]
+    fn edl() {
+        let regions = vec![
+            region("Scene 1-01", "Scene 1.wav", 48000, 96000, 48000 * 2),
+            region("Room Tone", "room-tone take2.aif", 48000 * 4, 0, 24000),
+        ];
+        let track = Track {
+            name: "DX 1".to_string(),
+            regions: regions.clone(),
+            channels: vec![channel(0, regions)],
+            ..Default::default()
+        };
+        let mut session = PtSession {
+            session_sample_rate: 48000,
+            timecode_rate: Some(TimecodeRate::Fps25),
+            ..Default::default()
+        };
+        assert_eq!(to_string(&session, &track).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        session.session_start = Some(48000 * 3600);
+
+        assert_eq!(to_string(&session, &track).unwrap(), "\
+TITLE: DX 1
+FCM: NON-DROP FRAME
+
+001  SCENE1   A     C        00:00:02:00 00:00:04:00 01:00:01:00 01:00:03:00
+* FROM CLIP NAME: Scene 1-01
+* SOURCE FILE: Scene 1.wav
+
+002  ROOMTONE A     C        00:00:00:00 00:00:00:12 01:00:04:00 01:00:04:12
+* FROM CLIP NAME: Room Tone
+* SOURCE FILE: room-tone take2.aif
+
+");
+    }
+
+    #[test]
+    fn channels() {
+        // Split stereo files whose names only differ past the reel length
+        let track = Track {
+            name: "MX".to_string(),
+            channels: vec![
+                channel(0, vec![region("Score", "Score Cue 1.L.wav", 0, 0, 48000)]),
+                channel(1, vec![region("Score", "Score Cue 1.R.wav", 0, 0, 48000)]),
+            ],
+            ..Default::default()
+        };
+        let session = PtSession {
+            session_sample_rate: 48000,
+            timecode_rate: Some(TimecodeRate::Fps25),
+            session_start: Some(0),
+            ..Default::default()
+        };
+        let edl = to_string(&session, &track).unwrap();
+        let events: Vec<&str> = edl.lines().filter(|line| line.starts_with("00")).collect();
+        assert_eq!(events, [
+            "001  SCORECUE A     C        00:00:00:00 00:00:01:00 00:00:00:00 00:00:01:00",
+            "002  SCORECU2 A2    C        00:00:00:00 00:00:01:00 00:00:00:00 00:00:01:00",
+        ]);
+
+        let surround = Track { channels: vec![channel(0, vec![]); 6], ..track };
+        assert!(to_string(&session, &surround).is_err());
+    }
+
+    #[test]
+    fn events() {
+        let session = PtSession {
+            session_sample_rate: 48000,
+            timecode_rate: Some(TimecodeRate::Fps25),
+            session_start: Some(0),
+            ..Default::default()
+        };
+        let regions = |count: u64| (0..count)
+            .map(|i| region("Take", "Take.wav", i * 48000, 0, 48000))
+            .collect::<Vec<_>>();
+        let track = Track { name: "DX".to_string(), regions: regions(999), ..Default::default() };
+        let edl = to_string(&session, &track).unwrap();
+        assert!(edl.contains("\n999  TAKE "));
+
+        let track = Track { regions: regions(1000), ..track };
+        assert_eq!(to_string(&session, &track).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -0,0 +1,245 @@
+//! Marker lists, subtitles and label tracks.
+
+use crate::session::{Marker, PtSession};
+
+use std::io::{self, Write};
+use std::time::Duration;
+
+/// Length of subtitle cues and labels, which markers do not have.
+/// A cue ends early when the next marker starts before it is over.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CueDuration(pub Duration);
+
+impl Default for CueDuration {
+    fn default() -> Self {
+        CueDuration(Duration::from_secs(2))
+    }
+}
+
+/// Writes the markers as CSV with sample, timecode, min:sec and seconds columns.
+/// Fails with `InvalidInput` when the timecode rate or start of the session is not known.
+pub fn write_csv<W: Write>(out: &mut W, session: &PtSession) -> io::Result<()> {
+    let timeline = session.timeline()?;
+    writeln!(out, "Index,Name,Timecode,Min:Sec,Seconds,Samples,Comment")?;
+    for marker in &session.markers {
+        let pos = marker.sample_offset as u64;
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{}",
+            marker.index,
+            csv_field(&marker.name),
+            timeline.timecode(pos),
+            timeline.min_sec(pos),
+            timeline.seconds(pos),
+            pos,
+            csv_field(&marker.comment)
+        )?;
+    }
+    Ok(())
+}
+
+/// Writes the markers as SubRip subtitles, the comment below the marker name.
+pub fn write_srt<W: Write>(out: &mut W, session: &PtSession, duration: CueDuration) -> io::Result<()> {
+    for (i, (marker, start, end)) in cues(session, duration).enumerate() {
+        writeln!(out, "{}", i + 1)?;
+        writeln!(out, "{} --> {}", cue_time(start, ','), cue_time(end, ','))?;
+        writeln!(out, "{}", cue_text(marker))?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes the markers as WebVTT subtitles, the comment below the marker name.
+pub fn write_webvtt<W: Write>(out: &mut W, session: &PtSession, duration: CueDuration) -> io::Result<()> {
+    writeln!(out, "WEBVTT")?;
+    writeln!(out)?;
+    for (marker, start, end) in cues(session, duration) {
+        let text = cue_text(marker)
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;");
+        writeln!(out, "{} --> {}", cue_time(start, '.'), cue_time(end, '.'))?;
+        writeln!(out, "{}", text)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Writes the markers as an Audacity label track, named after the marker with its comment.
+pub fn write_audacity<W: Write>(out: &mut W, session: &PtSession, duration: CueDuration) -> io::Result<()> {
+    for (marker, start, end) in cues(session, duration) {
+        let label = if marker.comment.is_empty() {
+            marker.name.clone()
+        } else {
+            format!("{}: {}", marker.name, marker.comment)
+        };
+        writeln!(
+            out,
+            "{:.6}\t{:.6}\t{}",
+            start.as_secs_f64(),
+            end.as_secs_f64(),
+            label.replace(['\t', '\r', '\n'], " ")
+        )?;
+    }
+    Ok(())
+}
+
+// Markers with their start and end, in order of position
+fn cues(session: &PtSession, duration: CueDuration) -> impl Iterator<Item = (&Marker, Duration, Duration)> {
+    let rate = session.session_sample_rate.max(1);
+    let time = move |samples: usize| Duration::from_nanos((samples as u128 * 1_000_000_000 / rate as u128) as u64);
+
+    let mut markers: Vec<&Marker> = session.markers.iter().collect();
+    markers.sort_by_key(|marker| marker.sample_offset);
+    let next_starts: Vec<Option<Duration>> = markers
+        .iter()
+        .skip(1)
+        .map(|marker| Some(time(marker.sample_offset)))
+        .chain(Some(None))
+        .collect();
+
+    markers.into_iter().zip(next_starts).map(move |(marker, next)| {
+        let start = time(marker.sample_offset);
+        let end = start + duration.0;
+        let end = next.filter(|next| *next > start).map_or(end, |next| end.min(next));
+        (marker, start, end)
+    })
+}
+
+// HH:MM:SS,mmm for SubRip and HH:MM:SS.mmm for WebVTT
+fn cue_time(time: Duration, separator: char) -> String {
+    let millis = time.as_millis();
+    format!(
+        "{:02}:{:02}:{:02}{}{:03}",
+        millis / 3_600_000,
+        millis / 60_000 % 60,
+        millis / 1000 % 60,
+        separator,
+        millis % 1000
+    )
+}
+
+// An empty line ends a cue and an arrow starts the next one's timing line
+fn cue_text(marker: &Marker) -> String {
+    let text = if marker.comment.is_empty() {
+        marker.name.clone()
+    } else {
+        format!("{}\n{}", marker.name, marker.comment)
+    };
+    text.replace("-->", "->")
+        .replace("\r\n", "\n")
+        .replace('\r', "\n")
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::timecode::TimecodeRate;
+
+    fn session() -> PtSession {
+        let marker = |index, name: &str, comment: &str, sample_offset| Marker {
+            name: name.to_string(),
+            index,
+            comment: comment.to_string(),
+            sample_offset,
+            ..Default::default()
+        };
+        PtSession {
+            session_sample_rate: 48000,
+            timecode_rate: Some(TimecodeRate::Fps25),
+            session_start: Some(0),
+            markers: vec![
+                marker(1, "Scene 1", "Fix \"pop\", then <re-print>", 48000 * 61 + 24000),
+                marker(2, "Scene 2", "", 48000 * 62),
+            ],
+            ..Default::default()
+        }
+    }
+
+    fn output<F: Fn(&mut Vec<u8>) -> io::Result<()>>(write: F) -> String {
+        let mut out = vec![];
+        write(&mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn csv() {
+        assert_eq!(output(|out| write_csv(out, &session())), "\
+Index,Name,Timecode,Min:Sec,Seconds,Samples,Comment
+1,Scene 1,00:01:01:12,1:01.500,61.5,2952000,\"Fix \"\"pop\"\", then <re-print>\"
+2,Scene 2,00:01:02:00,1:02.000,62,2976000,
+");
+    }
+
+    #[test]
+    fn subtitles() {
+        let duration = CueDuration(Duration::from_secs(1));
+        assert_eq!(output(|out| write_srt(out, &session(), duration)), "\
+1
+00:01:01,500 --> 00:01:02,000
+Scene 1
+Fix \"pop\", then <re-print>
+
+2
+00:01:02,000 --> 00:01:03,000
+Scene 2
+
+");
+        assert_eq!(output(|out| write_webvtt(out, &session(), duration)), "\
+WEBVTT
+
+00:01:01.500 --> 00:01:02.000
+Scene 1
+Fix \"pop\", then &lt;re-print&gt;
+
+00:01:02.000 --> 00:01:03.000
+Scene 2
+
+");
+    }
+
+    #[test]
+    fn cue_separators() {
+        let mut session = session();
+        session.markers.truncate(1);
+        session.markers[0].comment = "Cut --> here\r\n \r\rthen fade".to_string();
+        let duration = CueDuration(Duration::from_secs(1));
+        assert_eq!(output(|out| write_srt(out, &session, duration)), "\
+1
+00:01:01,500 --> 00:01:02,500
+Scene 1
+Cut -> here
+then fade
+
+");
+        assert_eq!(output(|out| write_webvtt(out, &session, duration)), "\
+WEBVTT
+
+00:01:01.500 --> 00:01:02.500
+Scene 1
+Cut -&gt; here
+then fade
+
+");
+    }
+
+    #[test]
+    fn audacity() {
+        assert_eq!(output(|out| write_audacity(out, &session(), CueDuration::default())), "\
+61.500000\t62.000000\tScene 1: Fix \"pop\", then <re-print>
+62.000000\t64.000000\tScene 2
+");
+    }
+}
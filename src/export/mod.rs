@@ -0,0 +1,59 @@
+//! Conversion of parsed sessions into formats of other applications.
+
+pub mod ardour;
+pub mod edl;
+pub mod markers;
+pub mod otio;
+pub mod reaper;
+
+use crate::session::Wav;
+
+use std::io;
+
+/// Directory next to the session file that Pro Tools records and imports audio into
+pub const AUDIO_FILES_DIR: &str = "Audio Files";
+
+/// Path of `wav` relative to the session directory
+pub(crate) fn audio_file_path(wav: &Wav) -> String {
+    format!("{}/{}", AUDIO_FILES_DIR, wav.file_name)
+}
+
+/// What `write` writes, as a string
+pub(crate) fn try_to_string<F>(write: F) -> io::Result<String>
+where
+    F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+{
+    let mut out = vec![];
+    write(&mut out)?;
+    Ok(String::from_utf8_lossy(&out).into_owned())
+}
+
+/// What `write` writes, as a string, for writers that only fail when the output does
+pub(crate) fn to_string<F>(write: F) -> String
+where
+    F: FnOnce(&mut Vec<u8>) -> io::Result<()>,
+{
+    try_to_string(write).expect("Writing to a Vec does not fail")
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use crate::session::{Channel, Region, Wav};
+
+    /// A region of `file_name`, placed at `start_pos`
+    pub fn region(name: &str, file_name: &str, start_pos: u64, sample_offset: u64, len: usize) -> Region {
+        Region {
+            name: name.to_string(),
+            start_pos,
+            sample_offset,
+            len,
+            wav: Some(Wav { file_name: file_name.to_string(), ..Default::default() }),
+            ..Default::default()
+        }
+    }
+
+    /// A channel holding `regions`
+    pub fn channel(index: u16, regions: Vec<Region>) -> Channel {
+        Channel { index, regions, ..Default::default() }
+    }
+}
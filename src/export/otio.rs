@@ -0,0 +1,218 @@
+//! OpenTimelineIO documents.
+
+use super::audio_file_path;
+use crate::session::{PtSession, Region};
+
+use serde_json::{json, Value};
+
+use std::io::{self, Write};
+
+/// The session as an OTIO Timeline named `name`, with one audio track per channel of
+/// each [`Track`]. Channels of multichannel tracks are numbered after the track name.
+///
+/// All times are at the session sample rate. Media references point at the audio
+/// files relative to the session directory. The global start time is only set when
+/// the session start is known.
+pub fn to_value(session: &PtSession, name: &str) -> Value {
+    let rate = session.session_sample_rate;
+    let tracks: Vec<Value> = session.audio_tracks
+        .iter()
+        .flat_map(|track| {
+            let channels = track.channel_regions();
+            let num_channels = channels.len();
+            channels.into_iter().enumerate().map(move |(i, regions)| {
+                let name = if num_channels > 1 {
+                    format!("{} {}", track.name, i + 1)
+                } else {
+                    track.name.clone()
+                };
+                otio_track(&name, regions, rate)
+            })
+        })
+        .collect();
+    let markers: Vec<Value> = session.markers
+        .iter()
+        .map(|marker| json!({
+            "OTIO_SCHEMA": "Marker.2",
+            "name": marker.name,
+            "comment": marker.comment,
+            "color": "RED",
+            "marked_range": time_range(marker.sample_offset as u64, 0, rate),
+            "metadata": {},
+        }))
+        .collect();
+
+    json!({
+        "OTIO_SCHEMA": "Timeline.1",
+        "name": name,
+        "global_start_time": session.session_start.map(|start| rational_time(start, rate)),
+        "metadata": {},
+        "tracks": {
+            "OTIO_SCHEMA": "Stack.1",
+            "name": "tracks",
+            "children": tracks,
+            "markers": markers,
+            "effects": [],
+            "metadata": {},
+            "source_range": null,
+        },
+    })
+}
+
+/// Writes the OTIO JSON document of the session, see [`to_value`].
+pub fn write<W: Write>(out: &mut W, session: &PtSession, name: &str) -> io::Result<()> {
+    serde_json::to_writer_pretty(&mut *out, &to_value(session, name))?;
+    writeln!(out)
+}
+
+/// OTIO JSON document of the session as a string, see [`to_value`].
+pub fn to_string(session: &PtSession, name: &str) -> String {
+    serde_json::to_string_pretty(&to_value(session, name)).expect("OTIO values serialize")
+}
+
+fn otio_track(name: &str, regions: Vec<Region>, rate: u64) -> Value {
+    let mut children = vec![];
+    let mut end = 0;
+
+    // Regions do not overlap within a Pro Tools track, Gaps fill the space between them
+    for region in regions {
+        let start = region.start_pos.max(end);
+        let region_end = region.start_pos + region.len as u64;
+        if start >= region_end {
+            continue;
+        }
+        if start > end {
+            children.push(json!({
+                "OTIO_SCHEMA": "Gap.1",
+                "name": "",
+                "source_range": time_range(0, start - end, rate),
+                "effects": [],
+                "markers": [],
+                "metadata": {},
+            }));
+        }
+        children.push(clip(&region, start - region.start_pos, region_end - start, rate));
+        end = region_end;
+    }
+
+    json!({
+        "OTIO_SCHEMA": "Track.1",
+        "name": name,
+        "kind": "Audio",
+        "children": children,
+        "effects": [],
+        "markers": [],
+        "metadata": {},
+        "source_range": null,
+    })
+}
+
+fn clip(region: &Region, trim: u64, len: u64, rate: u64) -> Value {
+    let media_reference = match &region.wav {
+        Some(wav) => json!({
+            "OTIO_SCHEMA": "ExternalReference.1",
+            "name": wav.file_name,
+            "target_url": audio_file_path(wav),
+            "available_range": null,
+            "metadata": {},
+        }),
+        None => json!({
+            "OTIO_SCHEMA": "MissingReference.1",
+            "name": "",
+            "available_range": null,
+            "metadata": {},
+        }),
+    };
+
+    json!({
+        "OTIO_SCHEMA": "Clip.2",
+        "name": region.name,
+        "source_range": time_range(region.sample_offset + trim, len, rate),
+        "media_references": { "DEFAULT_MEDIA": media_reference },
+        "active_media_reference_key": "DEFAULT_MEDIA",
+        "enabled": true,
+        "effects": [],
+        "markers": [],
+        "metadata": {},
+    })
+}
+
+fn rational_time(value: u64, rate: u64) -> Value {
+    json!({
+        "OTIO_SCHEMA": "RationalTime.1",
+        "rate": rate as f64,
+        "value": value as f64,
+    })
+}
+
+fn time_range(start: u64, duration: u64, rate: u64) -> Value {
+    json!({
+        "OTIO_SCHEMA": "TimeRange.1",
+        "start_time": rational_time(start, rate),
+        "duration": rational_time(duration, rate),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{export::test, session::{Marker, Track}};
+
+    #[test]
+    fn otio() {
+        let region = |name, start_pos, len| test::region(name, "DX.wav", start_pos, 100, len);
+        let channel = |index, file_name| test::channel(index, vec![test::region("Score", file_name, 0, 0, 48000)]);
+        let mut session = PtSession {
+            session_sample_rate: 48000,
+            audio_tracks: vec![
+                Track {
+                    name: "DX".to_string(),
+                    regions: vec![region("A", 48000, 1000), region("B", 49500, 500)],
+                    ..Default::default()
+                },
+                Track {
+                    name: "MX".to_string(),
+                    channels: vec![channel(1, "Score.L.wav"), channel(2, "Score.R.wav")],
+                    ..Default::default()
+                },
+            ],
+            markers: vec![Marker {
+                name: "Scene 2".to_string(),
+                comment: "Check sync".to_string(),
+                sample_offset: 49500,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let timeline = to_value(&session, "Reel 1");
+        assert_eq!(timeline["name"], "Reel 1");
+        let children = timeline["tracks"]["children"][0]["children"].as_array().unwrap();
+        let schemas: Vec<_> = children.iter().map(|c| c["OTIO_SCHEMA"].as_str().unwrap()).collect();
+        assert_eq!(schemas, vec!["Gap.1", "Clip.2", "Gap.1", "Clip.2"]);
+        assert_eq!(children[0]["source_range"]["duration"]["value"], 48000.0);
+        assert_eq!(children[1]["source_range"]["start_time"]["value"], 100.0);
+        assert_eq!(children[2]["source_range"]["duration"]["value"], 500.0);
+        assert_eq!(
+            children[3]["media_references"]["DEFAULT_MEDIA"]["target_url"],
+            "Audio Files/DX.wav"
+        );
+
+        let marker = &timeline["tracks"]["markers"][0];
+        assert_eq!(marker["comment"], "Check sync");
+        assert_eq!(marker["marked_range"]["start_time"]["rate"], 48000.0);
+
+        // Every channel of a multichannel track
+        let tracks = timeline["tracks"]["children"].as_array().unwrap();
+        let names: Vec<_> = tracks.iter().map(|t| t["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["DX", "MX 1", "MX 2"]);
+        assert_eq!(
+            tracks[2]["children"][0]["media_references"]["DEFAULT_MEDIA"]["target_url"],
+            "Audio Files/Score.R.wav"
+        );
+
+        assert!(timeline["global_start_time"].is_null());
+        session.session_start = Some(48000 * 3600);
+        assert_eq!(to_value(&session, "Reel 1")["global_start_time"]["value"], 48000.0 * 3600.0);
+    }
+}
@@ -0,0 +1,183 @@
+//! Reaper projects.
+
+use super::audio_file_path;
+use crate::session::{PtSession, Track};
+
+use std::io::{self, Write};
+
+/// Writes the session as a Reaper project (`.rpp`).
+///
+/// Multichannel tracks become a folder holding one track per channel, as their
+/// audio is stored in one file per channel. Reaper markers have no comment, so
+/// comments are appended to the marker name.
+pub fn write<W: Write>(out: &mut W, session: &PtSession) -> io::Result<()> {
+    let rate = session.session_sample_rate;
+
+    writeln!(out, "<REAPER_PROJECT 0.1 \"6.0\" 0")?;
+    writeln!(out, "  SAMPLERATE {} 0 0", rate)?;
+
+    for (index, marker) in session.markers.iter().enumerate() {
+        let name = if marker.comment.is_empty() {
+            marker.name.clone()
+        } else {
+            format!("{} - {}", marker.name, marker.comment)
+        };
+        writeln!(
+            out,
+            "  MARKER {} {} {} 0",
+            index + 1,
+            seconds(marker.sample_offset as u64, rate),
+            quote(&name)
+        )?;
+    }
+
+    for track in &session.audio_tracks {
+        if track.channels.len() > 1 {
+            write_track_header(out, &track.name, track.channels.len(), Folder::Parent)?;
+            writeln!(out, "  >")?;
+
+            let channels = track.split_channels();
+            for (i, channel) in channels.iter().enumerate() {
+                let folder = if i + 1 == channels.len() { Folder::Last } else { Folder::None };
+                let name = format!("{} {}", track.name, i + 1);
+                write_track(out, &Track { name, ..channel.clone() }, rate, folder)?;
+            }
+        } else {
+            write_track(out, track, rate, Folder::None)?;
+        }
+    }
+
+    writeln!(out, ">")
+}
+
+/// The Reaper project as a string, see [`write`].
+pub fn to_string(session: &PtSession) -> String {
+    super::to_string(|out| write(out, session))
+}
+
+#[derive(Clone, Copy)]
+enum Folder {
+    None,
+    Parent,
+    Last,
+}
+
+fn write_track_header<W: Write>(out: &mut W, name: &str, num_channels: usize, folder: Folder) -> io::Result<()> {
+    writeln!(out, "  <TRACK")?;
+    writeln!(out, "    NAME {}", quote(name))?;
+    // Reaper tracks have an even number of channels, at least two
+    writeln!(out, "    NCHAN {}", (num_channels.max(2) + 1) / 2 * 2)?;
+    match folder {
+        Folder::None => writeln!(out, "    ISBUS 0 0"),
+        Folder::Parent => writeln!(out, "    ISBUS 1 1"),
+        Folder::Last => writeln!(out, "    ISBUS 2 -1"),
+    }
+}
+
+fn write_track<W: Write>(out: &mut W, track: &Track, rate: u64, folder: Folder) -> io::Result<()> {
+    write_track_header(out, &track.name, 1, folder)?;
+
+    for region in track.flattened_regions() {
+        let wav = match &region.wav {
+            Some(wav) => wav,
+            None => continue,
+        };
+        writeln!(out, "    <ITEM")?;
+        writeln!(out, "      POSITION {}", seconds(region.start_pos, rate))?;
+        writeln!(out, "      LENGTH {}", seconds(region.len as u64, rate))?;
+        writeln!(out, "      SOFFS {}", seconds(region.sample_offset, rate))?;
+        writeln!(out, "      NAME {}", quote(&region.name))?;
+        writeln!(out, "      <SOURCE WAVE")?;
+        writeln!(out, "        FILE {}", quote(&audio_file_path(wav)))?;
+        writeln!(out, "      >")?;
+        writeln!(out, "    >")?;
+    }
+
+    writeln!(out, "  >")
+}
+
+fn seconds(samples: u64, rate: u64) -> f64 {
+    samples as f64 / rate as f64
+}
+
+// Reaper quotes strings with whichever quote character they do not contain
+fn quote(s: &str) -> String {
+    ['"', '\'', '`']
+        .iter()
+        .find(|q| !s.contains(**q))
+        .map_or_else(|| format!("\"{}\"", s.replace('"', "'")), |q| format!("{}{}{}", q, s, q))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{export::test, session::Marker};
+
+    #[test]
+    fn rpp() {
+        let region = |name, file_name| test::region(name, file_name, 48000, 24000, 96000);
+        let channel = |index, name, file_name| test::channel(index, vec![region(name, file_name)]);
+        let session = PtSession {
+            session_sample_rate: 48000,
+            audio_tracks: vec![
+                Track {
+                    name: "DX".to_string(),
+                    regions: vec![region("Take \"2\"", "DX.wav")],
+                    channels: vec![channel(0, "Take \"2\"", "DX.wav")],
+                    ..Default::default()
+                },
+                Track {
+                    name: "MX".to_string(),
+                    channels: vec![channel(1, "Score.L", "Score.L.wav"), channel(2, "Score.R", "Score.R.wav")],
+                    ..Default::default()
+                },
+            ],
+            markers: vec![Marker {
+                name: "Scene 2".to_string(),
+                comment: "Check sync".to_string(),
+                sample_offset: 72000,
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let item = |name: &str, file_name: &str| format!("    <ITEM
+      POSITION 1
+      LENGTH 2
+      SOFFS 0.5
+      NAME {}
+      <SOURCE WAVE
+        FILE \"Audio Files/{}\"
+      >
+    >
+", name, file_name);
+        let expected = format!("\
+<REAPER_PROJECT 0.1 \"6.0\" 0
+  SAMPLERATE 48000 0 0
+  MARKER 1 1.5 \"Scene 2 - Check sync\" 0
+  <TRACK
+    NAME \"DX\"
+    NCHAN 2
+    ISBUS 0 0
+{}  >
+  <TRACK
+    NAME \"MX\"
+    NCHAN 2
+    ISBUS 1 1
+  >
+  <TRACK
+    NAME \"MX 1\"
+    NCHAN 2
+    ISBUS 0 0
+{}  >
+  <TRACK
+    NAME \"MX 2\"
+    NCHAN 2
+    ISBUS 2 -1
+{}  >
+>
+", item("'Take \"2\"'", "DX.wav"), item("\"Score.L\"", "Score.L.wav"), item("\"Score.R\"", "Score.R.wav"));
+
+        assert_eq!(to_string(&session), expected);
+    }
+}
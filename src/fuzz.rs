@@ -0,0 +1,12 @@
+//! Entry points for the fuzz targets in `fuzz/`, built with the `fuzzing` feature.
+
+use crate::{parser::PtSessionParser, session::Block};
+
+use std::io;
+
+pub use crate::decrypt::unxor;
+
+/// Parses the block at `pos` of an already decrypted session, along with its children.
+pub fn parse_block_at(ptf_unxored: Vec<u8>, is_bigendian: bool, pos: usize) -> Result<Block, io::Error> {
+    PtSessionParser::from_unxored(ptf_unxored, is_bigendian).parse_block_at(pos, None)
+}
@@ -1,8 +1,17 @@
+pub mod diff;
+pub mod encoding;
 pub mod error;
+pub mod export;
+#[cfg(feature = "fuzzing")]
+pub mod fuzz;
+pub mod media;
 pub mod parser;
 pub mod session;
+pub mod session_info;
+pub mod timecode;
 mod read_traits;
 mod content_description;
 mod decrypt;
 
 pub use session::PtSession;
+pub use content_description::PTCD;
@@ -0,0 +1,477 @@
+use ptsession::{
+    error::PtError,
+    export::{self, ardour, edl, otio, reaper},
+    media::{Media, MediaReport},
+    parser::PtSessionParser,
+    session::{Block, Track},
+    session_info::{self, TimeFormat},
+    timecode::{Timecode, TimecodeRate, Timeline},
+    PtSession,
+};
+
+use std::env;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+const USAGE: &str = "\
+Usage: ptsession [options] <command> <session>...
+       ptsession [options] edl|otio|reaper|ardour|text <session>
+       ptsession [options] diff <old session> <new session>
+
+Options:
+    --lenient       Skip what does not parse instead of failing, with warnings on stderr
+    --fps <rate>    Timecode rate of the sessions: 23.976, 24, 25, 29.97, 29.97df or 30
+    --start <tc>    Session start timecode, needs --fps
+    --track <name>  Audio track the EDL is of, when the session has several
+    --search <dir>  Folder media also looks for missing audio files in, can be repeated
+
+Session files do not store a timecode rate and start that can be read, edl needs both
+to be given. text writes them in its header when they are given.
+
+Commands:
+    info       Summary of files, regions and tracks
+    json       Session serialized as JSON
+    markers    Memory locations
+    tracks     Audio and MIDI tracks with their regions
+    files      Audio files used by the session
+    plugins    Plugins used by the session and the tracks they are inserted on
+    io         I/O channels, paths and track routing
+    edl        CMX3600 EDL of an audio track
+    otio       OpenTimelineIO timeline
+    reaper     Reaper project
+    ardour     Ardour session
+    text       Session info as exported by Pro Tools as text
+    media      Audio files found next to the session, missing or ambiguous
+    diff       Changes between two sessions
+    blocks     Raw block tree
+
+Exit codes:
+    0    Success
+    1    The sessions compared by diff differ
+    2    Invalid arguments
+    3    Could not read a session
+    4    Not a Pro Tools session
+    5    Unsupported Pro Tools version
+    6    Could not parse a session
+    7    Audio files of a session are missing or ambiguous
+    8    Could not serialize the sessions as JSON";
+
+const EXIT_DIFFERENT: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_MISSING_MEDIA: i32 = 7;
+const EXIT_SERIALIZE: i32 = 8;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Command {
+    Info,
+    Json,
+    Markers,
+    Tracks,
+    Files,
+    Plugins,
+    Io,
+    Edl,
+    Otio,
+    Reaper,
+    Ardour,
+    Text,
+    Media,
+    Diff,
+    Blocks,
+}
+
+impl Command {
+    fn from_arg(arg: &str) -> Option<Self> {
+        use Command::*;
+        match arg {
+            "info" => Some(Info),
+            "json" => Some(Json),
+            "markers" => Some(Markers),
+            "tracks" => Some(Tracks),
+            "files" => Some(Files),
+            "plugins" => Some(Plugins),
+            "io" => Some(Io),
+            "edl" => Some(Edl),
+            "otio" => Some(Otio),
+            "reaper" => Some(Reaper),
+            "ardour" => Some(Ardour),
+            "text" => Some(Text),
+            "media" => Some(Media),
+            "diff" => Some(Diff),
+            "blocks" => Some(Blocks),
+            _ => None,
+        }
+    }
+
+    // Commands that write a single document, which several sessions would break
+    fn is_document(self) -> bool {
+        matches!(self, Command::Edl | Command::Otio | Command::Reaper | Command::Ardour | Command::Text)
+    }
+
+    // Commands that write timecode
+    fn needs_timeline(self) -> bool {
+        matches!(self, Command::Edl)
+    }
+}
+
+#[derive(Default)]
+struct Options {
+    lenient: bool,
+    timecode_rate: Option<TimecodeRate>,
+    start: Option<Timecode>,
+    track: Option<String>,
+    search_roots: Vec<String>,
+}
+
+impl Options {
+    // Options and the remaining arguments, `None` when an option is invalid
+    fn parse(mut args: impl Iterator<Item = String>) -> Option<(Self, Vec<String>)> {
+        let mut options = Options::default();
+        let mut start = None;
+        let mut rest = vec![];
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--lenient" => options.lenient = true,
+                "--fps" => options.timecode_rate = Some(args.next()?.parse().ok()?),
+                "--start" => start = Some(args.next()?),
+                "--track" => options.track = Some(args.next()?),
+                "--search" => options.search_roots.push(args.next()?),
+                _ => rest.push(arg),
+            }
+        }
+        if let Some(start) = start {
+            options.start = Some(Timecode::parse(&start, options.timecode_rate?)?);
+        }
+        Some((options, rest))
+    }
+
+    // Timecode the session file does not store
+    fn apply(&self, session: &mut PtSession) {
+        if let Some(rate) = self.timecode_rate {
+            session.timecode_rate = Some(rate);
+            if let (Some(start), Ok(timeline)) = (&self.start, Timeline::new(session.session_sample_rate, rate, 0)) {
+                session.session_start = timeline.position(start);
+            }
+        }
+    }
+}
+
+fn exit_code(err: &PtError) -> i32 {
+    use PtError::*;
+    match err {
+        Io(_) | Save(_) => 3,
+        Decrypt(_) | BitCode | Endianness => 4,
+        Version(_) => 5,
+        Parse(_) => 6,
+    }
+}
+
+fn main() {
+    if env::args().any(|arg| arg == "-h" || arg == "--help") {
+        println!("{}", USAGE);
+        return;
+    }
+
+    let (options, args) = Options::parse(env::args().skip(1)).unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        process::exit(EXIT_USAGE);
+    });
+    let command = match args.first().and_then(|arg| Command::from_arg(arg)) {
+        Some(Command::Diff) if args.len() != 3 => None,
+        Some(command) if command.is_document() && args.len() != 2 => None,
+        Some(command) if args.len() > 1 => Some(command),
+        _ => None,
+    };
+    let command = match command {
+        Some(command) => command,
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+    let paths = &args[1..];
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if command == Command::Diff {
+        let open = |path: &String| open(path, &options).unwrap_or_else(|err| {
+            eprintln!("{}: {}", path, err);
+            process::exit(exit_code(&err));
+        });
+        let diff = open(&paths[0]).diff(&open(&paths[1]));
+        let code = if diff.is_empty() { 0 } else { EXIT_DIFFERENT };
+        exit_on_broken_pipe(write!(out, "{}", diff), code);
+        process::exit(code);
+    }
+
+    let mut code = 0;
+    let mut sessions = vec![];
+
+    for path in paths {
+        if paths.len() > 1 && command != Command::Json {
+            exit_on_broken_pipe(writeln!(out, "==> {} <==", path), code);
+        }
+
+        let result = match command {
+            Command::Blocks => PtSessionParser::decrypt(path).and_then(|mut parser| {
+                let blocks = if options.lenient {
+                    let (blocks, diagnostics) = parser.blocks_lenient()?;
+                    for diagnostic in &diagnostics {
+                        eprintln!("{}: warning: {}", path, diagnostic);
+                    }
+                    blocks
+                } else {
+                    parser.blocks()?
+                };
+                exit_on_broken_pipe(print_blocks(&mut out, blocks), code);
+                Ok(())
+            }),
+            Command::Json => open(path, &options).map(|session| sessions.push(session)),
+            Command::Media => open(path, &options).map(|session| {
+                let report = session.resolve_media(session_dir(path), &options.search_roots);
+                exit_on_broken_pipe(print_media(&mut out, &report), code);
+                if !report.is_complete() && code == 0 {
+                    code = EXIT_MISSING_MEDIA;
+                }
+            }),
+            _ => open(path, &options).map(|session| match check(command, &session, &options) {
+                Ok(()) => exit_on_broken_pipe(print_session(&mut out, command, path, &session, &options), code),
+                Err(message) => {
+                    eprintln!("{}: {}", path, message);
+                    if code == 0 {
+                        code = EXIT_USAGE;
+                    }
+                }
+            }),
+        };
+
+        if let Err(err) = result {
+            eprintln!("{}: {}", path, err);
+            if code == 0 {
+                code = exit_code(&err);
+            }
+        }
+    }
+
+    if command == Command::Json && !sessions.is_empty() {
+        let json = if paths.len() > 1 {
+            serde_json::to_string_pretty(&sessions)
+        } else {
+            serde_json::to_string_pretty(&sessions[0])
+        };
+        match json {
+            Ok(json) => exit_on_broken_pipe(writeln!(out, "{}", json), code),
+            Err(err) => {
+                eprintln!("Could not serialize session: {}", err);
+                code = EXIT_SERIALIZE;
+            }
+        }
+    }
+
+    process::exit(code);
+}
+
+// In lenient mode what could not be parsed is reported on stderr
+fn open(path: &str, options: &Options) -> Result<PtSession, PtError> {
+    let mut session = if options.lenient {
+        let (session, diagnostics) = PtSession::open_lenient(path)?;
+        for diagnostic in &diagnostics {
+            eprintln!("{}: warning: {}", path, diagnostic);
+        }
+        session
+    } else {
+        PtSession::open(path)?
+    };
+    options.apply(&mut session);
+    Ok(session)
+}
+
+// What a command needs that the session or the options may not provide
+fn check(command: Command, session: &PtSession, options: &Options) -> Result<(), String> {
+    if command.needs_timeline() {
+        session.timeline().map_err(|err| format!("{}, give it with --fps and --start", err))?;
+    }
+    if command == Command::Edl {
+        let track = edl_track(session, options)?;
+        let channels = track.channel_regions();
+        if channels.len() > edl::MAX_CHANNELS {
+            return Err(format!("Track `{}` has more channels than an EDL", track.name));
+        }
+        // Regions without an audio file are left out
+        let events = channels.iter().flatten().filter(|region| region.wav.is_some()).count();
+        if events > edl::MAX_EVENTS {
+            return Err(format!("Track `{}` has {} events, an EDL has up to {}", track.name, events, edl::MAX_EVENTS));
+        }
+    }
+    Ok(())
+}
+
+// The track given with --track, which can be left out when the session has only one
+fn edl_track<'a>(session: &'a PtSession, options: &Options) -> Result<&'a Track, String> {
+    let tracks = &session.audio_tracks;
+    match &options.track {
+        Some(name) => tracks.iter().find(|t| t.name == *name).ok_or_else(|| format!("No audio track `{}`", name)),
+        None if tracks.len() == 1 => Ok(&tracks[0]),
+        None => {
+            let names: Vec<&str> = tracks.iter().map(|t| t.name.as_str()).collect();
+            Err(format!("Select an audio track with --track: {}", names.join(", ")))
+        }
+    }
+}
+
+// Stop quietly when the reader of our output went away, e.g. `ptsession blocks x.ptx | head`
+fn exit_on_broken_pipe(result: io::Result<()>, code: i32) {
+    if let Err(err) = result {
+        if err.kind() != io::ErrorKind::BrokenPipe {
+            eprintln!("Could not write output: {}", err);
+        }
+        process::exit(code);
+    }
+}
+
+fn print_session<W: Write>(
+    out: &mut W,
+    command: Command,
+    path: &str,
+    session: &PtSession,
+    options: &Options,
+) -> io::Result<()> {
+    match command {
+        Command::Info => write!(out, "{}", session)?,
+        Command::Markers => {
+            for marker in &session.markers {
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}",
+                    marker.index, marker.sample_offset, marker.name, marker.comment
+                )?;
+            }
+        }
+        Command::Tracks => {
+            let tracks = session.audio_tracks.iter().map(|t| (t.format().to_string(), t))
+                .chain(session.midi_tracks.iter().map(|t| ("MIDI".to_string(), t)));
+            for (kind, track) in tracks {
+                writeln!(out, "{}\t{}\t{}", track.index, kind, track.name)?;
+                for region in &track.flattened_regions() {
+                    writeln!(
+                        out,
+                        "\t{}\t{}\t{}\t{}",
+                        region.start_pos, region.sample_offset, region.len, region.name
+                    )?;
+                }
+            }
+        }
+        Command::Files => {
+            for wav in &session.audio_files {
+                writeln!(out, "{}\t{}\t{}", wav.index, wav.len, wav.file_name)?;
+            }
+        }
+        Command::Plugins => {
+            for plugin in &session.plugins {
+                writeln!(
+                    out,
+                    "{}\t{}/{}/{}\t{}\t{}\t{}",
+                    plugin.index,
+                    plugin.manufacturer_id,
+                    plugin.product_id,
+                    plugin.plugin_id,
+                    plugin.name,
+                    plugin.bundle_id,
+                    plugin.tracks.join(", ")
+                )?;
+            }
+        }
+        Command::Io => {
+            for channel in &session.io_channels {
+                writeln!(out, "{}\t{:?}\t{:?}\t{}", channel.index, channel.kind, channel.channels, channel.name)?;
+            }
+            for route in &session.io_routes {
+                writeln!(out, "{}\tPath\t{:?}\t{}\t{}", route.index, route.channels, route.name, route.ports.join(", "))?;
+            }
+            let tracks = session.audio_tracks.iter().chain(session.midi_tracks.iter());
+            for track in tracks {
+                writeln!(
+                    out,
+                    "{}\tTrack\t{}\t{}\t{}",
+                    track.index,
+                    track.name,
+                    track.input.as_deref().unwrap_or("-"),
+                    track.output.as_deref().unwrap_or("-")
+                )?;
+            }
+        }
+        Command::Edl => {
+            if let Ok(track) = edl_track(session, options) {
+                edl::write(out, session, track)?;
+            }
+        }
+        Command::Otio => otio::write(out, session, &session_name(path))?,
+        Command::Reaper => reaper::write(out, session)?,
+        Command::Ardour => {
+            let audio_dir = session_dir(path).join(export::AUDIO_FILES_DIR);
+            ardour::write(out, session, &session_name(path), &audio_dir.to_string_lossy())?;
+        }
+        Command::Text => session_info::write(out, session, &session_name(path), TimeFormat::Samples)?,
+        Command::Json | Command::Media | Command::Diff | Command::Blocks => {}
+    }
+    Ok(())
+}
+
+fn print_media<W: Write>(out: &mut W, report: &MediaReport) -> io::Result<()> {
+    for file in &report.files {
+        match &file.media {
+            Media::Found(path) => writeln!(out, "Found\t{}\t{}", file.wav.file_name, path.display())?,
+            Media::Missing => writeln!(out, "Missing\t{}", file.wav.file_name)?,
+            Media::Ambiguous(paths) => {
+                writeln!(out, "Ambiguous\t{}", file.wav.file_name)?;
+                for path in paths {
+                    writeln!(out, "\t{}", path.display())?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn session_dir(path: &str) -> &Path {
+    match Path::new(path).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    }
+}
+
+fn session_name(path: &str) -> String {
+    Path::new(path)
+        .file_stem()
+        .map_or(path.into(), |stem| stem.to_string_lossy().into_owned())
+}
+
+fn print_blocks<W: Write>(out: &mut W, blocks: &[Block]) -> io::Result<()> {
+    for block in blocks {
+        print_block(out, block, 0)?;
+    }
+    Ok(())
+}
+
+fn print_block<W: Write>(out: &mut W, block: &Block, level: usize) -> io::Result<()> {
+    let name = block.ptcd()
+        .map(|ptcd| format!("{:?}", ptcd))
+        .unwrap_or_default();
+    writeln!(
+        out,
+        "{:indent$}{:#06x} {} @ {}, {}",
+        "",
+        block.content_type,
+        name,
+        block.offset,
+        block.size,
+        indent = level * 2
+    )?;
+    for child in &block.children {
+        print_block(out, child, level + 1)?;
+    }
+    Ok(())
+}
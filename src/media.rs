@@ -0,0 +1,201 @@
+//! Finding the audio files of a session on disk.
+
+use crate::session::{PtSession, Wav};
+
+use serde::{Serialize, Deserialize};
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where an audio file of the session was found.
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum Media {
+    Found(PathBuf),
+    Missing,
+    /// Several files of the same name, none of them in the folder the session expects
+    Ambiguous(Vec<PathBuf>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct ResolvedWav {
+    pub wav: Wav,
+    pub media: Media,
+}
+
+/// The audio files of a session and where they were found, see [`PtSession::resolve_media`].
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct MediaReport {
+    pub files: Vec<ResolvedWav>,
+}
+
+impl MediaReport {
+    /// Whether every audio file was found exactly once.
+    pub fn is_complete(&self) -> bool {
+        self.files.iter().all(|file| matches!(file.media, Media::Found(_)))
+    }
+
+    pub fn missing(&self) -> impl Iterator<Item = &Wav> {
+        self.files.iter().filter(|file| file.media == Media::Missing).map(|file| &file.wav)
+    }
+
+    pub fn ambiguous(&self) -> impl Iterator<Item = &ResolvedWav> {
+        self.files.iter().filter(|file| matches!(file.media, Media::Ambiguous(_)))
+    }
+}
+
+impl PtSession {
+    /// Finds the audio files of the session, which is stored in `session_dir`.
+    ///
+    /// A file is looked for where the session last saw it relative to the session, then in
+    /// the session's `Audio Files` folder and the session folder itself. Failing that
+    /// `session_dir` and the `search_roots` are searched for a file of the same name, a
+    /// file in a folder named like the one it was added from is preferred.
+    pub fn resolve_media<P: AsRef<Path>, R: AsRef<Path>>(&self, session_dir: P, search_roots: &[R]) -> MediaReport {
+        let session_dir = session_dir.as_ref();
+        let names: Vec<OsString> = self.audio_files.iter().map(|wav| OsString::from(&wav.file_name)).collect();
+        let mut found: Option<HashMap<OsString, Vec<PathBuf>>> = None;
+
+        let files = self.audio_files.iter().map(|wav| {
+            let expected = self.relative_folder(wav)
+                .map(|folder| session_dir.join(folder))
+                .into_iter()
+                .chain([session_dir.join(crate::export::AUDIO_FILES_DIR), session_dir.to_path_buf()])
+                .map(|folder| folder.join(&wav.file_name))
+                .find(|path| path.is_file());
+
+            let media = match expected {
+                Some(path) => Media::Found(path),
+                None => {
+                    // Only walk the search roots when a file is not where it is expected
+                    let found = found.get_or_insert_with(|| {
+                        let mut found = HashMap::new();
+                        let roots = Some(session_dir).into_iter().chain(search_roots.iter().map(AsRef::as_ref));
+                        for root in roots {
+                            find_files(root, &names, &mut found);
+                        }
+                        found
+                    });
+                    let mut paths: Vec<PathBuf> = found.get(OsString::from(&wav.file_name).as_os_str())
+                        .cloned()
+                        .unwrap_or_default();
+                    paths.sort();
+                    paths.dedup();
+                    if paths.len() > 1 {
+                        let folder = wav.path.last().map(OsString::from);
+                        let in_folder: Vec<&PathBuf> = paths
+                            .iter()
+                            .filter(|path| folder.is_some() && path.parent().and_then(Path::file_name) == folder.as_deref())
+                            .collect();
+                        if let [path] = in_folder[..] {
+                            paths = vec![path.clone()];
+                        }
+                    }
+                    match paths.len() {
+                        0 => Media::Missing,
+                        1 => Media::Found(paths.remove(0)),
+                        _ => Media::Ambiguous(paths),
+                    }
+                }
+            };
+            ResolvedWav { wav: wav.clone(), media }
+        });
+
+        MediaReport { files: files.collect() }
+    }
+
+    // Folder of `wav` relative to the session's folder, when both are on the same volume
+    fn relative_folder(&self, wav: &Wav) -> Option<PathBuf> {
+        let session_folders = &self.session_path[..self.session_path.len().checked_sub(1)?];
+        let common = session_folders.iter().zip(&wav.path).take_while(|(a, b)| a == b).count();
+        if common == 0 {
+            return None;
+        }
+
+        let mut folder = PathBuf::new();
+        for _ in common..session_folders.len() {
+            folder.push("..");
+        }
+        for name in &wav.path[common..] {
+            folder.push(name);
+        }
+        Some(folder)
+    }
+}
+
+// Adds the files below `dir` that are named one of `names`, symlinked folders are not followed
+fn find_files(dir: &Path, names: &[OsString], found: &mut HashMap<OsString, Vec<PathBuf>>) {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        match entry.file_type() {
+            Ok(file_type) if file_type.is_dir() => find_files(&entry.path(), names, found),
+            Ok(_) => {
+                let name = entry.file_name();
+                if names.contains(&name) {
+                    found.entry(name).or_default().push(entry.path());
+                }
+            }
+            Err(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn resolve() {
+        let dir = std::env::temp_dir().join(format!("ptsession-media-{}", std::process::id()));
+        let session_dir = dir.join("Mix");
+        let archive = dir.join("Archive");
+        for folder in ["Mix/Audio Files", "Mix/Imported", "Archive/Reel 1", "Archive/Reel 2"] {
+            fs::create_dir_all(dir.join(folder)).unwrap();
+        }
+        for file in ["Mix/Audio Files/Dialog.wav", "Mix/Imported/Score.wav", "Archive/Reel 1/Fx.wav", "Archive/Reel 2/Fx.wav"] {
+            fs::write(dir.join(file), b"").unwrap();
+        }
+
+        let wav = |file_name: &str, path: &[&str]| Wav {
+            file_name: file_name.to_string(),
+            path: path.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        let session = PtSession {
+            session_path: vec!["HD".to_string(), "Mix".to_string(), "Mix.ptx".to_string()],
+            audio_files: vec![
+                wav("Dialog.wav", &[]),
+                wav("Score.wav", &["HD", "Mix", "Imported"]),
+                wav("Fx.wav", &["HD", "Library"]),
+                wav("Room Tone.wav", &[]),
+            ],
+            ..Default::default()
+        };
+
+        let report = session.resolve_media(&session_dir, &[&archive]);
+        let media: Vec<&Media> = report.files.iter().map(|file| &file.media).collect();
+        assert_eq!(media, [
+            &Media::Found(session_dir.join("Audio Files/Dialog.wav")),
+            &Media::Found(session_dir.join("Imported/Score.wav")),
+            &Media::Ambiguous(vec![archive.join("Reel 1/Fx.wav"), archive.join("Reel 2/Fx.wav")]),
+            &Media::Missing,
+        ]);
+        assert!(!report.is_complete());
+        assert_eq!(report.missing().next().unwrap().file_name, "Room Tone.wav");
+
+        // A file in a folder named like the one it was added from is taken
+        let mut session = session;
+        session.audio_files[2].path.push("Reel 2".to_string());
+        let report = session.resolve_media(&session_dir, &[&archive]);
+        assert_eq!(report.files[2].media, Media::Found(archive.join("Reel 2/Fx.wav")));
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
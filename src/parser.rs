@@ -1,5 +1,6 @@
 use crate::{
     content_description::PTCD,
+    encoding::{self, RawString},
     read_traits::*,
     error::*,
     session::*,
@@ -8,8 +9,12 @@ use crate::{
 
 use log::{debug, warn, trace};
 
-use std::io::Cursor;
+use std::collections::HashMap;
+use std::io::{Cursor, Write};
+use std::ops::Range;
 use std::convert::TryInto;
+use std::path::Path;
+use std::fs;
 
 macro_rules! filter_blocks {
     ($block_iter:expr, $child:expr) => {
@@ -38,14 +43,62 @@ macro_rules! children_of {
     };
 }
 
+// Audio tracks, the regions they are built from and region groups
+type AudioTracks = (Vec<Track>, Vec<Region>, Vec<RegionGroup>);
+
 #[derive(Default)]
 struct BlockMap {
+    blocks: Vec<Block>,
     wav_blocks: Vec<Block>,
     header_blocks: Vec<Block>,
     marker_blocks: Vec<Block>,
     region_to_wav_blocks: Vec<Block>,
     region_to_track_blocks: Vec<Block>,
+    region_group_blocks: Vec<Block>,
     track_blocks: Vec<Block>,
+    midi_event_blocks: Vec<Block>,
+    midi_region_blocks: Vec<Block>,
+    midi_region_to_track_blocks: Vec<Block>,
+    plugin_blocks: Vec<Block>,
+    io_channel_blocks: Vec<Block>,
+    io_route_blocks: Vec<Block>,
+    track_list_blocks: Vec<Block>,
+    track_io_blocks: Vec<Block>,
+    session_path_blocks: Vec<Block>,
+}
+
+// An entry of the WAV_Names list: a volume (0), folder (1) or file (2)
+struct WavNamesEntry {
+    name: String,
+    kind: u8,
+    parent: u32,
+}
+
+impl WavNamesEntry {
+    // Volume and folders of the file at `entries[index]`, empty when the list has none
+    fn folders(entries: &[WavNamesEntry], index: usize) -> Vec<String> {
+        let mut folders = vec![];
+        let mut entry = &entries[index];
+        if entry.kind != 2 {
+            return folders;
+        }
+        while entry.parent != u32::MAX {
+            match entries.get(entry.parent as usize) {
+                Some(parent) if parent.kind < 2 && folders.len() < entries.len() => {
+                    folders.push(parent.name.clone());
+                    entry = parent;
+                }
+                _ => return vec![],
+            }
+        }
+        folders.reverse();
+        folders
+    }
+}
+
+// An error for data that ends before what is expected of it
+fn truncated(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::UnexpectedEof, format!("{} is truncated", what))
 }
 
 pub struct PtSessionParser {
@@ -53,6 +106,8 @@ pub struct PtSessionParser {
     is_bigendian: bool,
     block_map: Option<BlockMap>,
     version: Option<u8>,
+    lenient: bool,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl Read for PtSessionParser {
@@ -91,9 +146,122 @@ impl PtSessionParser {
         self.reader.get_ref()
     }
 
-    pub fn decrypt<P: AsRef<std::path::Path>>(path: P) -> Result<Self, PtError> {
-        let ptf_unxored = decrypt::unxor(path)
-            .map_err(|e| PtError::Decrypt(e))?;
+    /// The top level blocks of the session, parsing them on first use.
+    pub fn blocks(&mut self) -> Result<&[Block], PtError> {
+        if self.block_map.is_none() {
+            self.parse_blocks()?;
+        }
+        Ok(&self.block_map.as_ref().unwrap().blocks)
+    }
+
+    /// The top level blocks of the session, skipping bytes that do not parse as a block.
+    /// What was skipped is listed in the diagnostics.
+    pub fn blocks_lenient(&mut self) -> Result<(&[Block], Vec<Diagnostic>), PtError> {
+        self.lenient = true;
+        self.diagnostics.clear();
+        self.parse_blocks()?;
+        let diagnostics = std::mem::take(&mut self.diagnostics);
+        Ok((&self.block_map.as_ref().unwrap().blocks, diagnostics))
+    }
+
+    /// All blocks of `content_type` at any depth, in file order. The blocks are copies,
+    /// so the parser can be used while holding them, e.g. for `block_data`.
+    pub fn find_blocks<T: Into<u16>>(&mut self, content_type: T) -> Result<Vec<Block>, PtError> {
+        let content_type = content_type.into();
+        Ok(self.blocks()?
+            .iter()
+            .flat_map(|block| block.find(content_type))
+            .cloned()
+            .collect())
+    }
+
+    /// The payload of `block`, the bytes following its content type.
+    pub fn block_data(&self, block: &Block) -> &[u8] {
+        block.payload(self.unxored())
+    }
+
+    /// Mutable access to the decrypted session, for patching it before `save`.
+    /// Patches can not change the length of the session, see `splice` for that.
+    pub fn unxored_mut(&mut self) -> &mut [u8] {
+        self.reader.get_mut()
+    }
+
+    /// Replaces `range` of the decrypted session with `bytes`, growing or shrinking
+    /// every block that contains it. The range has to lie within the payload of the
+    /// blocks it touches. Lengths and counts inside the payload, e.g. of a string,
+    /// are left to the caller.
+    pub fn splice(&mut self, range: Range<usize>, bytes: &[u8]) -> Result<(), PtError> {
+        let invalid = |message: String| PtError::Save(io::Error::new(io::ErrorKind::InvalidInput, message));
+        if range.start > range.end || range.end > self.unxored().len() {
+            return Err(invalid(format!("{:?} is not within the session", range)));
+        }
+
+        // Size fields of the enclosing blocks, the four bytes before their content type
+        let mut size_fields = vec![];
+        let mut stack: Vec<&Block> = self.blocks()?.iter().collect();
+        while let Some(block) = stack.pop() {
+            let (start, end) = (block.offset - 7, block.offset + block.size);
+            if range.end <= start || range.start >= end {
+                continue;
+            }
+            if range.start < block.offset + 2 || range.end > end {
+                return Err(invalid(format!("{:?} overlaps the header of the block at byte {}", range, start)));
+            }
+            size_fields.push((block.offset - 4, block.size));
+            stack.extend(block.children.iter());
+        }
+
+        let growth = bytes.len() as i64 - range.len() as i64;
+        for (pos, size) in size_fields {
+            let size: u32 = (size as i64 + growth).try_into()
+                .map_err(|_| invalid(format!("the block at byte {} can not be resized", pos - 3)))?;
+            let size = if self.is_bigendian { size.to_be_bytes() } else { size.to_le_bytes() };
+            self.reader.get_mut()[pos..pos + 4].copy_from_slice(&size);
+        }
+        self.reader.get_mut().splice(range, bytes.iter().cloned());
+        self.block_map = None;
+        Ok(())
+    }
+
+    /// Re-encrypts the (possibly patched) decrypted session.
+    pub fn encrypt(&self) -> Result<Vec<u8>, PtError> {
+        decrypt::xor(self.unxored().to_vec())
+            .map_err(PtError::Save)
+    }
+
+    /// Writes the re-encrypted session to `writer`.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), PtError> {
+        writer.write_all(&self.encrypt()?)
+            .map_err(PtError::Io)
+    }
+
+    /// Saves the re-encrypted session as a Pro Tools file at `path`.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), PtError> {
+        fs::write(path, self.encrypt()?)
+            .map_err(PtError::Io)
+    }
+
+    /// Decrypts the Pro Tools session file at `path`.
+    pub fn decrypt<P: AsRef<Path>>(path: P) -> Result<Self, PtError> {
+        let ptf = fs::read(path).map_err(PtError::Io)?;
+        Self::decrypt_buffer(ptf)
+    }
+
+    /// Decrypts a Pro Tools session held in memory.
+    pub fn decrypt_bytes(bytes: &[u8]) -> Result<Self, PtError> {
+        Self::decrypt_buffer(bytes.to_vec())
+    }
+
+    /// Decrypts a Pro Tools session read to the end from `reader`.
+    pub fn decrypt_reader<R: Read>(mut reader: R) -> Result<Self, PtError> {
+        let mut ptf = vec![];
+        reader.read_to_end(&mut ptf).map_err(PtError::Io)?;
+        Self::decrypt_buffer(ptf)
+    }
+
+    fn decrypt_buffer(ptf: Vec<u8>) -> Result<Self, PtError> {
+        let ptf_unxored = decrypt::unxor(ptf)
+            .map_err(PtError::Decrypt)?;
 
         // Check BitCode
         debug!("BitCode check...");
@@ -109,13 +277,7 @@ impl PtSessionParser {
             _ => return Err(PtError::Endianness),
         };
 
-        let reader = Cursor::new(ptf_unxored);
-        let mut session_parser = PtSessionParser {
-            reader,
-            is_bigendian,
-            block_map: None,
-            version: None,
-        };
+        let mut session_parser = PtSessionParser::from_unxored(ptf_unxored, is_bigendian);
 
         // Parse Version
         debug!("Parsing version...");
@@ -124,23 +286,72 @@ impl PtSessionParser {
         Ok(session_parser)
     }
 
+    // A parser over a decrypted session whose version is not known yet
+    pub(crate) fn from_unxored(ptf_unxored: Vec<u8>, is_bigendian: bool) -> Self {
+        PtSessionParser {
+            reader: Cursor::new(ptf_unxored),
+            is_bigendian,
+            block_map: None,
+            version: None,
+            lenient: false,
+            diagnostics: vec![],
+        }
+    }
+
     fn parse_str_at(&mut self, pos: usize) -> Result<String, io::Error> {
         self.set_position(pos);
         self.parse_str()
     }
 
     fn parse_str(&mut self) -> Result<String, io::Error> {
+        self.parse_name().map(|(string, _)| string)
+    }
+
+    fn parse_name_at(&mut self, pos: usize) -> Result<(String, Option<RawString>), io::Error> {
+        self.set_position(pos);
+        self.parse_name()
+    }
+
+    // A string along with its raw bytes when they were not valid UTF-8
+    fn parse_name(&mut self) -> Result<(String, Option<RawString>), io::Error> {
+        let start = self.position();
         let len = self.read_u32()? as usize;
         let pos = self.position();
-        let end = pos + len;
+        let end = pos.saturating_add(len);
         trace!("Parsing str. Start {} End {} Len {}", pos, end, len);
-        let string = unsafe { std::str::from_utf8_unchecked(&self.reader.get_ref()[pos..end]).into() };
+        let name = match self.reader.get_ref().get(pos..end) {
+            Some(bytes) => encoding::decode_name(bytes, self.version),
+            None => {
+                self.set_position(start);
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    format!("String of {} bytes runs past the end of the file", len),
+                ));
+            }
+        };
+        if let (string, Some(raw)) = &name {
+            trace!("Decoded {:?} as {:?} `{}`", raw.bytes, raw.encoding, string);
+        }
         self.set_position(end);
-        Ok(string)
+        Ok(name)
+    }
+
+    pub(crate) fn parse_block_at(&mut self, pos: usize, parent: Option<&Block>) -> Result<Block, io::Error> {
+        self.parse_nested_block_at(pos, parent, 0)
     }
 
-    fn parse_block_at(&mut self, pos: usize, parent: Option<&Block>, level: i32) -> Result<Block, io::Error> {
+    // Blocks nested deeper than `MAX_BLOCK_DEPTH` are not parsed, so hostile files cannot
+    // exhaust the stack
+    fn parse_nested_block_at(&mut self, pos: usize, parent: Option<&Block>, depth: usize) -> Result<Block, io::Error> {
         const Z_MARK: u8 = 0x5a;
+        const MAX_BLOCK_DEPTH: usize = 64;
+
+        if depth > MAX_BLOCK_DEPTH {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Blocks are nested more than {} deep", MAX_BLOCK_DEPTH),
+            ));
+        }
 
         let len = self.reader.get_ref().len();
         let max = match parent {
@@ -167,54 +378,57 @@ impl PtSessionParser {
             children: vec![],
         };
 
-        if block.size + block.offset > max {
+        if block.offset.checked_add(block.size).map_or(true, |end| end > max) {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
-                "Block is too large",
+                format!("Block {:#06x} is too large, {} bytes end past byte {}", block.content_type, block.size, max),
             ));
         }
 
         if (block.block_type & 0xff00) != 0 {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
-                "Invalid Block Type",
+                format!("Invalid Block Type {:#06x}", block.block_type),
             ));
         }
 
         // Parse the children
-        let mut child_jump = 0;
         let mut i = 1;
 
-        while i < block.size && pos + i + child_jump < max {
+        while i < block.size && pos + i < max {
             let p = pos + i;
-            child_jump = 0;
-            
-            if let Ok(child) = self.parse_block_at(p, Some(&block), level + 1) {
-                child_jump = child.size + 7;
-                block.children.push(child);
-            }
 
-            i += if child_jump > 0 { child_jump } else { 1 };
+            i += match self.parse_nested_block_at(p, Some(&block), depth + 1) {
+                Ok(child) => {
+                    let child_jump = child.size + 7;
+                    block.children.push(child);
+                    child_jump
+                }
+                Err(_) => 1,
+            };
         }
 
         Ok(block)
     }
 
     fn parse_version(&mut self) -> Result<(), PtError> {
-        match self.parse_block_at(0x1f, None, 0) {
+        match self.parse_block_at(0x1f, None) {
             Ok(block) => match block.content_type.try_into() {
                 Ok(PTCD::INFO_Version) => {
                     // old PT
                     let skip = self.parse_str_at(block.offset + 3)
-                        .map_err(PtError::Io)?
+                        .map_err(|e| self.parse_error(Section::Header, e))?
                         .len() + 8;
                     self.set_position(block.offset + 3 + skip);
-                    self.version = Some(self.read_u32().map_err(PtError::Io)? as u8);
+                    self.version = Some(self.read_u32().map_err(|e| self.parse_error(Section::Header, e))? as u8);
                 }
                 Ok(PTCD::INFO_Path_of_Session) => {
                     // new PT
                     self.set_position(block.offset + 20);
-                    let version = 2 + self.read_u32().map_err(PtError::Io)? as u8;
+                    let version = self.read_u32().map_err(|e| self.parse_error(Section::Header, e))?
+                        .checked_add(2)
+                        .and_then(|version| version.try_into().ok())
+                        .ok_or_else(|| PtError::Version("Invalid version number".into()))?;
                     self.version = Some(version);
                 }
                 _ => {
@@ -227,13 +441,13 @@ impl PtSessionParser {
             }
             Err(e) => {
                 warn!("Could not parse version block: {}", e);
-                let ptf_unxored = self.unxored();
-                let mut version = ptf_unxored[0x40];
+                let byte = |pos: usize| self.unxored().get(pos).copied().unwrap_or(0);
+                let mut version = byte(0x40);
                 if version == 0 {
-                    version = ptf_unxored[0x3d];
+                    version = byte(0x3d);
                 }
                 if version == 0 {
-                    version = ptf_unxored[0x3a] + 2;
+                    version = byte(0x3a).wrapping_add(2);
                 }
                 if version != 0 {
                     self.version = Some(version);
@@ -248,44 +462,180 @@ impl PtSessionParser {
     }
 
     pub fn parse_session(&mut self) -> Result<PtSession, PtError> {
+        self.lenient = false;
+        self.parse()
+    }
+
+    /// Parses as much of the session as possible. Blocks, markers and plugins that do not
+    /// parse are skipped, other sections that do not parse are left empty and a missing
+    /// sample rate is taken as 48 kHz. Everything worked around is listed in the
+    /// diagnostics, along with references that could not be resolved.
+    pub fn parse_session_lenient(&mut self) -> Result<(PtSession, Vec<Diagnostic>), PtError> {
+        self.lenient = true;
+        self.diagnostics.clear();
+        let session = self.parse()?;
+        Ok((session, std::mem::take(&mut self.diagnostics)))
+    }
+
+    fn parse(&mut self) -> Result<PtSession, PtError> {
         debug!("Parsing blocks...");
         self.parse_blocks()?;
 
         debug!("Parsing header...");
-        let session_sample_rate = self.parse_header()?;
+        let session_sample_rate = match self.parse_header() {
+            Ok(session_sample_rate) => session_sample_rate,
+            Err(err) => {
+                self.recover(err)?;
+                48000
+            }
+        };
+
+        debug!("Parsing session path...");
+        let session_path = self.section(Section::Header, |p, blocks| p.parse_session_path(blocks))?;
 
         debug!("Parsing audio files...");
-        let audio_files = self.parse_audio_files()
-            .map_err(PtError::Io)?;
+        let audio_files = self.section(Section::AudioFiles, |p, blocks| p.parse_audio_files(blocks))?;
 
         debug!("Parsing audio tracks...");
-        let (audio_tracks, audio_regions) = self.parse_audio_tracks(&audio_files)
-            .map_err(PtError::Io)?;
+        let (mut audio_tracks, audio_regions, region_groups) =
+            self.section(Section::AudioTracks, |p, blocks| p.parse_audio_tracks(blocks, &audio_files))?;
+
+        debug!("Parsing MIDI...");
+        let (mut midi_tracks, midi_regions) = self.section(Section::Midi, |p, blocks| p.parse_midi(blocks))?;
+
+        debug!("Parsing I/O...");
+        let (io_channels, io_routes) = self.section(Section::Io, |p, blocks| p.parse_io(blocks))?;
+        let inserts = self.section(Section::Io, |p, blocks| {
+            p.parse_track_io(blocks, audio_tracks.iter_mut().chain(midi_tracks.iter_mut()), &io_channels, &io_routes)
+        })?;
 
         debug!("Parsing markers...");
-        let markers = self.parse_markers()
-            .map_err(PtError::Io)?;
+        let markers = self.section(Section::Markers, |p, blocks| p.parse_markers(blocks))?;
+
+        debug!("Parsing plugins...");
+        let plugins = self.section(Section::Plugins, |p, blocks| p.parse_plugins(blocks, &inserts))?;
 
         let session = PtSession {
             version: self.version.unwrap(),
             session_sample_rate,
+            session_path,
             audio_files,
             audio_tracks,
+            region_groups,
             audio_regions,
+            midi_tracks,
+            midi_regions,
             markers,
+            plugins,
+            io_channels,
+            io_routes,
             ..Default::default()
         };
 
         Ok(session)
     }
 
+    // Parses a section, in lenient mode a section that fails is noted and left empty
+    fn section<T: Default, F>(&mut self, section: Section, parse: F) -> Result<T, PtError>
+    where
+        F: FnOnce(&mut Self, &BlockMap) -> Result<T, io::Error>,
+    {
+        // The block map is lent to the section, so it is back when the section fails
+        let block_map = self.block_map.take().expect("Blocks are parsed before the sections");
+        let result = parse(self, &block_map);
+        self.block_map = Some(block_map);
+        match result {
+            Ok(value) => Ok(value),
+            Err(e) => {
+                let err = self.parse_error(section, e).into();
+                self.recover(err)?;
+                Ok(T::default())
+            }
+        }
+    }
+
+    // Parses an entry of a section, in lenient mode an entry that fails is noted and skipped
+    fn entry<T, F>(&mut self, section: Section, parse: F) -> Result<Option<T>, io::Error>
+    where
+        F: FnOnce(&mut Self) -> Result<T, io::Error>,
+    {
+        match parse(self) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if self.lenient => {
+                let err = self.parse_error(section, e);
+                warn!("{}", err);
+                self.diagnostics.push(Diagnostic::from(err));
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    // Notes a parse error in lenient mode, other errors are returned
+    fn recover(&mut self, err: PtError) -> Result<(), PtError> {
+        match err {
+            PtError::Parse(err) if self.lenient => {
+                warn!("{}", err);
+                self.diagnostics.push(Diagnostic::from(err));
+                Ok(())
+            }
+            err => Err(err),
+        }
+    }
+
+    fn diagnose(&mut self, section: Section, offset: usize, message: String) {
+        warn!("{} at byte {}: {}", section, offset, message);
+        self.diagnostics.push(Diagnostic {
+            section,
+            offset: Some(offset),
+            block_path: vec![],
+            message,
+        });
+    }
+
+    // An error at the current position, in the blocks around it
+    fn parse_error(&mut self, section: Section, err: io::Error) -> ParseError {
+        let offset = self.position();
+        ParseError::io(section, err)
+            .at(offset)
+            .in_blocks(self.block_path(offset))
+    }
+
+    // Content types of the blocks around `offset`, outermost first
+    fn block_path(&mut self, offset: usize) -> Vec<u16> {
+        let mut path = vec![];
+        if let Some(block_map) = &self.block_map {
+            // Top level blocks are in file order, only the last one starting before
+            // `offset` can hold it
+            let i = block_map.blocks.partition_point(|block| block.offset - 7 <= offset);
+            if let Some(block) = i.checked_sub(1).map(|i| &block_map.blocks[i]) {
+                push_block_path(block, offset, &mut path);
+            }
+            return path;
+        }
+
+        // The blocks are not parsed yet while reading the version
+        let mut i = 20;
+        while i < self.unxored().len() {
+            let block = match self.parse_block_at(i, None) {
+                Ok(block) => block,
+                Err(_) => break,
+            };
+            if push_block_path(&block, offset, &mut path) {
+                break;
+            }
+            i += if block.size > 0 { block.size + 7 } else { 1 };
+        }
+        path
+    }
+
     fn parse_blocks(&mut self) -> Result<(), PtError> {
         let mut block_map = BlockMap::default();
         let mut i = 20;
         let mut count = 0;
 
         while i < self.unxored().len() {
-            match self.parse_block_at(i, None, 0) {
+            match self.parse_block_at(i, None) {
                 Ok(block) => {
                     count += 1;
                     i += if block.size > 0 {
@@ -295,26 +645,50 @@ impl PtSessionParser {
                     };
                     if let Ok(ptcd) = block.content_type.try_into() {
                         use PTCD::*;
-                        match ptcd {
-                            INFO_SampleRate => block_map.header_blocks.push(block),
-                            WAV_List_Full => block_map.wav_blocks.push(block),
-                            AUDIO_Region_List_v5 | AUDIO_Region_List_v10  => block_map.region_to_wav_blocks.push(block),
-                            AUDIO_Tracks => block_map.track_blocks.push(block),
-                            AUDIO_Region_Track_Full_Map | AUDIO_Region_Track_Full_Map_v8 => block_map.region_to_track_blocks.push(block),
-                            MARKER_List => block_map.marker_blocks.push(block),
-                            _ => {}
+                        let typed_blocks = match ptcd {
+                            INFO_SampleRate => Some(&mut block_map.header_blocks),
+                            WAV_List_Full => Some(&mut block_map.wav_blocks),
+                            AUDIO_Region_List_v5 | AUDIO_Region_List_v10  => Some(&mut block_map.region_to_wav_blocks),
+                            AUDIO_Tracks => Some(&mut block_map.track_blocks),
+                            COMPOUND_Region_Full_Map => Some(&mut block_map.region_group_blocks),
+                            AUDIO_Region_Track_Full_Map | AUDIO_Region_Track_Full_Map_v8 => Some(&mut block_map.region_to_track_blocks),
+                            MARKER_List => Some(&mut block_map.marker_blocks),
+                            MIDI_Events_Block => Some(&mut block_map.midi_event_blocks),
+                            MIDI_Regions_Map | MIDI_Regions_Map_v10 => Some(&mut block_map.midi_region_blocks),
+                            MIDI_Region_Track_Full_Map => Some(&mut block_map.midi_region_to_track_blocks),
+                            PLUGIN_Full_List => Some(&mut block_map.plugin_blocks),
+                            IO_Channel_List => Some(&mut block_map.io_channel_blocks),
+                            IO_Routing_Table => Some(&mut block_map.io_route_blocks),
+                            TRACK_Full_List => Some(&mut block_map.track_list_blocks),
+                            TRACK_IO_List => Some(&mut block_map.track_io_blocks),
+                            INFO_Path_of_Session => Some(&mut block_map.session_path_blocks),
+                            _ => None,
+                        };
+                        if let Some(typed_blocks) = typed_blocks {
+                            typed_blocks.push(block.clone());
                         }
                     }
+                    block_map.blocks.push(block);
+                }
+                Err(e) if self.lenient => {
+                    // Skip to the next byte a block parses at
+                    let skipped = i;
+                    i += 1;
+                    while i < self.unxored().len() && self.parse_block_at(i, None).is_err() {
+                        i += 1;
+                    }
+                    self.diagnose(Section::Blocks, skipped, format!("Skipped {} bytes: {}", i - skipped, e));
                 }
                 Err(e) => {
                     debug!("Parsed {} parent blocks", count);
                     self.block_map = Some(block_map);
-                    return Err(PtError::Io(e));
+                    return Err(ParseError::io(Section::Blocks, e).at(i).into());
                 }
             }
         }
 
-        Ok(self.block_map = Some(block_map))
+        self.block_map = Some(block_map);
+        Ok(())
     }
 
     fn parse_header(&mut self) -> Result<u64, PtError> {
@@ -322,18 +696,40 @@ impl PtSessionParser {
 
         debug!("Header blocks: {}", block_map.header_blocks.len());
         if block_map.header_blocks.is_empty() {
-            return Err(PtError::Parse)
+            return Err(ParseError::new(Section::Header, "No sample rate block").into())
         }
 
         let offset = block_map.header_blocks[0].offset;
         self.set_position(offset + 4);
-        Ok(self.read_u32().map_err(PtError::Io)? as u64)
+        Ok(self.read_u32().map_err(|e| self.parse_error(Section::Header, e))? as u64)
     }
 
-    fn parse_audio_files(&mut self) -> Result<Vec<Wav>, io::Error> {
+    // The volume, folders and file name the session was saved as
+    fn parse_session_path(&mut self, block_map: &BlockMap) -> Result<Vec<String>, io::Error> {
+        let mut path = vec![];
+
+        if let Some(block) = block_map.session_path_blocks.first() {
+            // Document info fields, then the volume, folders and file name
+            self.set_position(block.offset + 24);
+            for _ in 0..5 {
+                self.parse_str()?;
+            }
+            self.increment_position(24);
+            let num_folders = self.read_u32()?;
+            for _ in 0..=num_folders {
+                if self.position() + 4 > block.offset + block.size {
+                    return Err(truncated("Session path"));
+                }
+                path.push(self.parse_str()?);
+            }
+        }
+
+        Ok(path)
+    }
+
+    fn parse_audio_files(&mut self, block_map: &BlockMap) -> Result<Vec<Wav>, io::Error> {
         let mut audio_files = vec![];
-        let block_map = self.block_map.take();
-        let wav_blocks = &block_map.as_ref().unwrap().wav_blocks;
+        let wav_blocks = &block_map.wav_blocks;
 
         for wav_list in wav_blocks {
             self.set_position(wav_list.offset + 2);
@@ -341,18 +737,26 @@ impl PtSessionParser {
             debug!("Num Wavs: {}", num_waves);
 
             for child in children_of!(wav_list, PTCD::WAV_Names) {
-                self.set_position(child.offset + 11);
+                self.set_position(child.offset + 6);
                 let mut n = 0;
+                let mut entries = vec![];
+                let mut wav_entries = vec![];
 
                 debug!("Found WAV @pos {} offset {} size {}", self.position(), child.offset, child.size);
 
-                while self.position() < child.offset + child.size && n < num_waves {
-                    let wav_name = self.parse_str()?;
-                    let wav_type =
-                        unsafe { std::str::from_utf8_unchecked(&self.reader.get_ref()[self.position()..(self.position() + 4)]).to_string() };
-                    self.increment_position(9);
-
-                    if wav_name.contains(".grp")
+                // Volumes and folders are listed along with the files, each entry
+                // refers to the entry of the folder it is in
+                while self.position() + 5 < child.offset + child.size {
+                    let entry_offset = self.position();
+                    let kind = self.read_u8()?;
+                    let parent = self.read_u32()?;
+                    let (wav_name, raw_name) = self.parse_name()?;
+                    let mut wav_type = [0u8; 4];
+                    self.read_exact(&mut wav_type)?;
+                    entries.push(WavNamesEntry { name: wav_name.clone(), kind, parent });
+
+                    if n >= num_waves
+                        || wav_name.contains(".grp")
                         || wav_name.contains("Audio Files")
                         || wav_name.contains("Fade Files")
                     {
@@ -360,37 +764,41 @@ impl PtSessionParser {
                     }
 
                     // Cull container types
-                    if self.version.unwrap() < 10 {
-                        if !(wav_type.contains("WAVE")
-                            || wav_type.contains("EVAW")
-                            || wav_type.contains("AIFF")
-                            || wav_type.contains("FFIA"))
-                        {
-                            continue;
-                        }
+                    let has_type = wav_type.iter().any(|&b| b != 0);
+                    let audio_type = [b"WAVE", b"EVAW", b"AIFF", b"FFIA"].contains(&&wav_type);
+                    let is_audio = if self.version.unwrap() < 10 || has_type {
+                        audio_type
                     } else {
-                        if wav_type.len() != 0 {
-                            if !(wav_type.contains("WAVE")
-                                || wav_type.contains("EVAW")
-                                || wav_type.contains("AIFF")
-                                || wav_type.contains("FFIA"))
-                            {
-                                continue;
-                            }
-                        } else if !(wav_name.contains(".wav") || wav_name.contains(".aif")) {
-                            continue;
+                        wav_name.contains(".wav") || wav_name.contains(".aif")
+                    };
+                    if !is_audio {
+                        // Folders have no type, newer sessions tell files apart
+                        if (kind == 2 || self.version.unwrap() < 10) && has_type {
+                            self.diagnose(
+                                Section::AudioFiles,
+                                entry_offset,
+                                format!("Skipped `{}` of unrecognised type {:?}", wav_name, String::from_utf8_lossy(&wav_type)),
+                            );
                         }
+                        continue;
                     }
 
                     let wav = Wav {
                         index: n as u16,
                         file_name: wav_name,
+                        raw_name,
                         ..Default::default()
                     };
 
+                    wav_entries.push(entries.len() - 1);
                     audio_files.push(wav);
                     n += 1;
                 }
+
+                let first = audio_files.len() - wav_entries.len();
+                for (wav, entry) in audio_files[first..].iter_mut().zip(wav_entries) {
+                    wav.path = WavNamesEntry::folders(&entries, entry);
+                }
             }
         }
 
@@ -402,17 +810,16 @@ impl PtSessionParser {
             }
         }
 
-        self.block_map = block_map;
         Ok(audio_files)
     }
 
-    fn parse_audio_tracks(&mut self, audio_files: &[Wav]) -> Result<(Vec<Track>, Vec<Region>), io::Error> {
+    fn parse_audio_tracks(&mut self, block_map: &BlockMap, audio_files: &[Wav]) -> Result<AudioTracks, io::Error> {
         const MAX_CHANNELS_PER_TRACK: usize = 8;
-        let mut audio_tracks: Vec<Track> = vec![];
+        let mut channel_tracks: Vec<Track> = vec![];
+        let mut track_channels = vec![];
         let mut regions = vec![];
-        let block_map = self.block_map.take();
-        let BlockMap { track_blocks, region_to_track_blocks, region_to_wav_blocks, .. }
-            = &block_map.as_ref().unwrap();
+        let BlockMap { track_blocks, region_to_track_blocks, region_to_wav_blocks, region_group_blocks, .. }
+            = block_map;
 
 
         let mut channel_map = [0u16; MAX_CHANNELS_PER_TRACK];
@@ -422,12 +829,19 @@ impl PtSessionParser {
         for block in region_to_wav_blocks {
             for b in children_of!(block, PTCD::AUDIO_Region_Name_Number_v5, PTCD::AUDIO_Region_Name_Number_v10) {
                 self.set_position(b.offset + 11);
-                let mut region = self.parse_region_info(b.offset + b.size)?;
-                if let Some(wav) = audio_files
-                    .iter()
-                    .find(|wav| region.wav.as_ref().unwrap().index == wav.index)
-                {
-                    region.wav.as_mut().unwrap().file_name = wav.file_name.clone();
+                // The wav index follows the region info block
+                let info_end = b.children
+                    .first()
+                    .map_or(b.offset + b.size, |info| info.offset + info.size);
+                let mut region = self.parse_region_info(info_end)?;
+                let wav_index = region.wav.as_ref().unwrap().index;
+                match audio_files.iter().find(|wav| wav.index == wav_index) {
+                    Some(wav) => region.wav.as_mut().unwrap().file_name = wav.file_name.clone(),
+                    None => self.diagnose(
+                        Section::AudioRegions,
+                        b.offset,
+                        format!("Region `{}` refers to unknown audio file {}", region.name, wav_index),
+                    ),
                 }
                 region.index = region_index;
                 regions.push(region);
@@ -435,29 +849,33 @@ impl PtSessionParser {
             }
         }
 
+        // Regions -> Region groups, in lenient mode tracks are placed without the groups
+        // when they do not parse
+        let region_groups = self
+            .entry(Section::AudioRegions, |p| p.parse_region_groups(region_group_blocks, &regions))?
+            .unwrap_or_default();
+
         // Audio Tracks
         for b in filter_blocks!(track_blocks.iter(), PTCD::AUDIO_Track_Name_Number) {
             self.set_position(b.offset + 2);
-            let name = self.parse_str()?;
+            let (name, raw_name) = self.parse_name()?;
 
             self.increment_position(1);
             let num_channels = self.read_u32()? as usize;
 
-            for i in 0..num_channels {
-                channel_map[i] = self.read_u16()?;
-                if audio_tracks
-                    .iter()
-                    .find(|&t| t.index == channel_map[i])
-                    .is_none()
-                {
+            for channel in channel_map.iter_mut().take(num_channels) {
+                *channel = self.read_u16()?;
+                if !channel_tracks.iter().any(|t| t.index == *channel) {
                     let track = Track {
-                        index: channel_map[i],
+                        index: *channel,
                         name: name.clone(),
+                        raw_name: raw_name.clone(),
                         ..Default::default()
                     };
-                    audio_tracks.push(track);
+                    channel_tracks.push(track);
                 }
             }
+            track_channels.push((name, raw_name, channel_map[..num_channels.min(MAX_CHANNELS_PER_TRACK)].to_vec()));
         }
 
         // Regions -> Tracks
@@ -465,34 +883,69 @@ impl PtSessionParser {
             match block.content_type.try_into() {
                 // Old PT
                 Ok(PTCD::AUDIO_Region_Track_Full_Map) => {
+                    for (count, a) in children_of!(block, PTCD::AUDIO_Region_Track_Map_Entries).enumerate() {
+                        let track_name = self.parse_str_at(a.offset + 2)?;
+                        trace!("Mapping regions for track {}", track_name);
 
+                        for c in filter_blocks!(children_of!(a, PTCD::AUDIO_Region_Track_Entry), PTCD::AUDIO_Region_Track_SubEntry) {
+                            self.set_position(c.offset + 4);
+                            let raw_index = self.read_u32()? as u16;
+
+                            // Old sessions place regions at the start stored in the region info
+                            let track_index = count as u16;
+                            if let Some(ref mut track) = channel_tracks.iter_mut().find(|t| t.index == track_index) {
+                                match regions.iter().find(|r| r.index == raw_index) {
+                                    Some(region) => track.regions.push(region.clone()),
+                                    None => self.diagnose(
+                                        Section::AudioTracks,
+                                        c.offset,
+                                        format!("Track `{}` refers to unknown region {}", track_name, raw_index),
+                                    ),
+                                }
+                            }
+                        }
+                    }
                 }
                 // New PT
                 Ok(PTCD::AUDIO_Region_Track_Full_Map_v8) => {
                     let mut count = 0;
 
-                    for a in children_of!(block, 0x1052) {
+                    for a in children_of!(block, PTCD::AUDIO_Region_Track_Map_Entries_v8) {
                         let track_name = self.parse_str_at(a.offset + 2)?;
                         trace!("Mapping regions for track {}", track_name);
 
-                        for b in children_of!(a, 0x1050) {
+                        for b in children_of!(a, PTCD::AUDIO_Region_Track_Entry_v8) {
                             // Check if region is fade
-                            if self.unxored()[b.offset + 46] == 0x01 {
+                            let fade = self.unxored().get(b.offset + 46).copied();
+                            if fade.ok_or_else(|| truncated("Region entry"))? == 0x01 {
                                 continue;
                             }
 
-                            for c in children_of!(b, 0x104f) {
+                            for c in children_of!(b, PTCD::AUDIO_Region_Track_SubEntry_v8) {
                                 self.set_position(c.offset + 4);
                                 let raw_index = self.read_u32()? as u16;
                                 self.increment_position(5);
                                 let start = self.read_u32()?;
 
                                 let track_index = count;
-                                if let Some(ref mut track) = audio_tracks.iter_mut().find(|t| t.index == track_index) {
+                                if let Some(ref mut track) = channel_tracks.iter_mut().find(|t| t.index == track_index) {
                                     if let Some(region) = regions.iter_mut().find(|r| r.index == raw_index) {
-                                        // start as f32 * rate_factor
                                         region.start_pos = start as u64;
                                         track.regions.push(region.clone());
+                                    } else if let Some(group) = (raw_index as usize)
+                                        .checked_sub(regions.len())
+                                        .and_then(|i| region_groups.get(i))
+                                    {
+                                        track.groups.push(RegionGroup {
+                                            start_pos: start as u64,
+                                            ..group.clone()
+                                        });
+                                    } else {
+                                        self.diagnose(
+                                            Section::AudioTracks,
+                                            c.offset,
+                                            format!("Track `{}` refers to unknown region {}", track_name, raw_index),
+                                        );
                                     }
                                 }
                             }
@@ -504,17 +957,88 @@ impl PtSessionParser {
             }
         }
 
-        self.block_map = block_map;
-        Ok((audio_tracks, regions))
+        // Channels -> Tracks
+        let audio_tracks = track_channels
+            .into_iter()
+            .filter_map(|(name, raw_name, indices)| {
+                let channels: Vec<Channel> = indices
+                    .iter()
+                    .filter_map(|index| channel_tracks.iter().find(|t| t.index == *index))
+                    .map(|t| Channel {
+                        index: t.index,
+                        regions: t.regions.clone(),
+                        groups: t.groups.clone(),
+                    })
+                    .collect();
+                let first = channels.first()?;
+
+                Some(Track {
+                    name,
+                    raw_name,
+                    index: first.index,
+                    regions: first.regions.clone(),
+                    groups: first.groups.clone(),
+                    channels,
+                    ..Default::default()
+                })
+            })
+            .collect();
+
+        Ok((audio_tracks, regions, region_groups))
+    }
+
+    // Region groups (compound regions) are stored like regions, with an element per member
+    // region holding its index and its placement relative to the start of the group.
+    // Groups are placed on tracks with indices following the audio regions.
+    // No session with groups was available, the region info block in the group map is
+    // assumed from the layout of audio regions.
+    fn parse_region_groups(&mut self, blocks: &[Block], regions: &[Region]) -> Result<Vec<RegionGroup>, io::Error> {
+        let mut region_groups = vec![];
+
+        for b in filter_blocks!(blocks.iter(), PTCD::COMPOUND_Region_Group) {
+            let name = self.parse_str_at(b.offset + 2)?;
+            let (sample_offset, start, len) = self.parse_three_point()?;
+            trace!("Found region group {}", name);
+
+            let mut members = vec![];
+            for e in children_of!(b, PTCD::COMPOUND_Region_element) {
+                self.set_position(e.offset + 2);
+                let region_index = self.read_u32()? as u16;
+                let (sample_offset, start, len) = self.parse_three_point()?;
+
+                match regions.iter().find(|r| r.index == region_index) {
+                    Some(region) => members.push(Region {
+                        start_pos: start as u64,
+                        sample_offset: sample_offset as u64,
+                        len,
+                        ..region.clone()
+                    }),
+                    None => self.diagnose(
+                        Section::AudioRegions,
+                        e.offset,
+                        format!("Region group `{}` refers to unknown region {}", name, region_index),
+                    ),
+                }
+            }
+
+            region_groups.push(RegionGroup {
+                name,
+                index: region_groups.len() as u16,
+                start_pos: start as u64,
+                sample_offset: sample_offset as u64,
+                len,
+                regions: members,
+            });
+        }
+
+        Ok(region_groups)
     }
 
     fn parse_region_info(&mut self, offset: usize) -> Result<Region, io::Error> {
-        let name = self.parse_str()?;
+        let (name, raw_name) = self.parse_name()?;
         let (sample_offset, start, length) = self.parse_three_point()?;
         self.set_position(offset);
         let index = self.read_u32()? as u16;
-        // let pos_absolute = start as f32 * rate_factor;
-        // let len = length as f32 * rate_factor
 
         let wav = Wav {
             index,
@@ -525,6 +1049,7 @@ impl PtSessionParser {
 
         let region = Region {
             name,
+            raw_name,
             start_pos: start as u64,
             sample_offset: sample_offset as u64,
             len: length,
@@ -535,45 +1060,449 @@ impl PtSessionParser {
         Ok(region)
     }
 
-    fn parse_markers(&mut self) -> Result<Vec<Marker>, io::Error> {
-        let block_map = self.block_map.take();
-        let marker_blocks = &block_map.as_ref().unwrap().marker_blocks;
-        let mut markers = vec![];
+    fn parse_midi(&mut self, block_map: &BlockMap) -> Result<(Vec<Track>, Vec<Region>), io::Error> {
+        let mut midi_tracks = vec![];
+        let mut regions = vec![];
+        let BlockMap { midi_event_blocks, midi_region_blocks, midi_region_to_track_blocks, .. }
+            = block_map;
+
+        // MIDI events, chunks do not map 1:1 to regions yet
+        let mut chunks = vec![];
+        for block in midi_event_blocks {
+            chunks.append(&mut self.parse_midi_chunks(block)?);
+        }
+
+        // MIDI chunks -> Regions
+        let mut region_index = 0;
+        for block in midi_region_blocks {
+            for c in children_of!(block, PTCD::MIDI_Region_Name_Number_v5, PTCD::MIDI_Region_Name_Number_v10) {
+                for d in children_of!(c, PTCD::REGION_Name_Number) {
+                    self.set_position(d.offset + 2);
+                    let mut region = self.parse_region_info(d.offset + d.size)?;
+                    // The index following MIDI region info refers to an event chunk, not a wav
+                    let chunk_index = region.wav.take().map(|wav| wav.index as usize);
+                    if let Some(events) = chunk_index.and_then(|i| chunks.get(i)) {
+                        region.midi_events = events.clone();
+                    }
+                    region.index = region_index;
+                    regions.push(region);
+                    region_index += 1;
+                }
+            }
+        }
+
+        // Regions -> MIDI Tracks
+        for block in midi_region_to_track_blocks {
+            for (count, a) in children_of!(block, PTCD::MIDI_Region_Track_Map_Entries).enumerate() {
+                let (name, raw_name) = self.parse_name_at(a.offset + 2)?;
+                trace!("Mapping regions for MIDI track {}", name);
+
+                let mut track = Track {
+                    name,
+                    raw_name,
+                    index: count as u16,
+                    ..Default::default()
+                };
+
+                for c in filter_blocks!(children_of!(a, PTCD::MIDI_Region_Track_Entry), PTCD::AUDIO_Region_Track_SubEntry_v8) {
+                    self.set_position(c.offset + 4);
+                    let raw_index = self.read_u32()? as u16;
+                    self.increment_position(1);
+                    let start = self.read_u40()?;
+
+                    if let Some(region) = regions.iter().find(|r| r.index == raw_index) {
+                        track.regions.push(Region {
+                            start_pos: start,
+                            ..region.clone()
+                        });
+                    }
+                }
+                midi_tracks.push(track);
+            }
+        }
+
+        Ok((midi_tracks, regions))
+    }
+
+    fn parse_midi_chunks(&mut self, block: &Block) -> Result<Vec<Vec<MidiEvent>>, io::Error> {
+        const CHUNK_MARK: &[u8] = b"MdNLB";
+        const EVENT_SIZE: usize = 35;
+
+        let end = block.offset + block.size;
+        let mut chunks = vec![];
+        let mut k = block.offset;
+
+        while k + EVENT_SIZE < end {
+            match self.unxored()[k..end]
+                .windows(CHUNK_MARK.len())
+                .position(|window| window == CHUNK_MARK)
+            {
+                Some(found) => k += found,
+                None => break,
+            }
 
-        for block in filter_blocks!(marker_blocks.iter(), PTCD::MARKER_List_Full => PTCD::MARKER_List_Entry) {
-            trace!("In Marker Entry");
-            self.set_position(block.offset + 2);
-            let index = self.read_u16()?;
-            self.increment_position(4);
-            let name = self.parse_str()?;
-            let sample_offset = self.read_u32()? as usize;
-
-            // Jump to marker comments
-            // NOTE: This method could break very easily!
-            // We are in full assumption mode that there are no further 0x01 bytes between the
-            // sample_offset and the comments
-            let mut pos = self.position();
-            let mut i = 0;
-            loop {
-                if self.read_u8()? == 0x01 {
-                    pos += i + 5;
+            self.set_position(k + 11);
+            let num_events = self.read_u32()?;
+            k = self.position();
+            // Event positions are relative to the first event of the chunk
+            let zero_ticks = self.read_u40()?;
+            trace!("MIDI chunk with {} events @ {}", num_events, k);
+
+            let mut events = vec![];
+            for _ in 0..num_events {
+                if k + EVENT_SIZE > self.unxored().len() {
                     break;
                 }
-                i += 1;
+                self.set_position(k);
+                let pos = self.read_u40()?.saturating_sub(zero_ticks);
+                self.set_position(k + 8);
+                let note = self.read_u8()?;
+                let len = self.read_u40()?;
+                self.set_position(k + 17);
+                let velocity = self.read_u8()?;
+
+                events.push(MidiEvent { pos, len, note, velocity });
+                k += EVENT_SIZE;
             }
+            chunks.push(events);
+        }
 
-            let comment = self.parse_str_at(pos)?;
+        Ok(chunks)
+    }
 
-            markers.push(Marker {
-                index,
+    // Each marker list is a ruler, named by its OBJECT_Name_Id. The entries are laid out as
+    // in Pro Tools 10 and later, older sessions are read the same way without knowing if it fits.
+    fn parse_markers(&mut self, block_map: &BlockMap) -> Result<Vec<Marker>, io::Error> {
+        let mut markers = vec![];
+        for list in &block_map.marker_blocks {
+            if let Some(version) = self.version.filter(|&version| version < 10) {
+                self.diagnose(
+                    Section::Markers,
+                    list.offset,
+                    format!("Marker entries of Pro Tools {} sessions are read with the Pro Tools 10 layout", version),
+                );
+            }
+            let ruler = match children_of!(list, PTCD::OBJECT_Name_Id).next() {
+                Some(metadata) => self.entry(Section::Markers, |p| p.parse_str_at(metadata.offset + 2))?.unwrap_or_default(),
+                None => String::new(),
+            };
+
+            for block in filter_blocks!(children_of!(list, PTCD::MARKER_List_Full), PTCD::MARKER_List_Entry) {
+                trace!("In Marker Entry");
+                if let Some(marker) = self.entry(Section::Markers, |p| p.parse_marker(block, &ruler))? {
+                    markers.push(marker);
+                }
+            }
+        }
+        Ok(markers)
+    }
+
+    // The number, name, start and end of a marker, then fields that hold its comment twice
+    // and its id, before any child blocks. The comment is the string these fields repeat,
+    // the other fields are not read, so their sizes are not assumed.
+    fn parse_marker(&mut self, block: &Block, ruler: &str) -> Result<Marker, io::Error> {
+        // Child blocks, such as an OBJECT_Name_Id, follow the fields of the entry
+        let end = block.children.first().map_or(block.offset + block.size, |child| child.offset - 7);
+
+        self.set_position(block.offset + 2);
+        let index = self.read_u16()?;
+        self.increment_position(4);
+        let (name, raw_name) = self.parse_name()?;
+        let sample_offset = self.read_u64()?;
+        // The end of the marker, which repeats its start
+        self.increment_position(8);
+        if self.position() > end {
+            return Err(truncated("Marker entry"));
+        }
+
+        let (comment, raw_comment) = match self.find_repeated_string(self.position(), end) {
+            Some(pos) => {
+                self.set_position(pos);
+                self.parse_name()?
+            }
+            // An empty comment is only its length
+            None => (String::new(), None),
+        };
+
+        Ok(Marker {
+            index,
+            name,
+            raw_name,
+            sample_offset,
+            comment,
+            raw_comment,
+            ruler: ruler.to_string(),
+        })
+    }
+
+    // Position of the first non-empty string in `start..end` that is repeated after itself.
+    // Names hold no NUL bytes, so the length of one, whose high bytes are 0, is never
+    // taken from the middle of another, and the strings are read once each.
+    fn find_repeated_string(&self, start: usize, end: usize) -> Option<usize> {
+        let bytes = self.unxored().get(start..end)?;
+        let mut first_at: HashMap<&[u8], usize> = HashMap::new();
+        let mut found: Option<usize> = None;
+
+        for i in 0..bytes.len() {
+            let len = match bytes.get(i..i + 4) {
+                Some(&[a, b, c, d]) if self.is_bigendian => u32::from_be_bytes([a, b, c, d]),
+                Some(&[a, b, c, d]) => u32::from_le_bytes([a, b, c, d]),
+                _ => break,
+            };
+            let string = match (len as usize).checked_add(i + 4).and_then(|end| bytes.get(i..end)) {
+                Some(string) if len > 0 && !string[4..].contains(&0) => string,
+                _ => continue,
+            };
+            match first_at.get(string) {
+                Some(&first) if first + string.len() <= i => {
+                    found = Some(found.map_or(first, |found| found.min(first)));
+                }
+                Some(_) => {}
+                None => {
+                    first_at.insert(string, i);
+                }
+            }
+        }
+        found.map(|i| start + i)
+    }
+
+    // The plugins of the session, with the tracks of the `inserts` that use them
+    fn parse_plugins(&mut self, block_map: &BlockMap, inserts: &[(String, Plugin)]) -> Result<Vec<Plugin>, io::Error> {
+        let mut plugins = vec![];
+        for block in filter_blocks!(block_map.plugin_blocks.iter(), PTCD::PLUGIN_Entry) {
+            let index = plugins.len() as u16;
+            if let Some(mut plugin) = self.entry(Section::Plugins, |p| p.parse_plugin(block, index))?.flatten() {
+                for (track, insert) in inserts {
+                    let same_plugin = (&insert.manufacturer_id, &insert.product_id, &insert.plugin_id)
+                        == (&plugin.manufacturer_id, &plugin.product_id, &plugin.plugin_id);
+                    if same_plugin && !plugin.tracks.contains(track) {
+                        plugin.tracks.push(track.clone());
+                    }
+                }
+                plugins.push(plugin);
+            }
+        }
+        Ok(plugins)
+    }
+
+    // A plugin entry, or None for an empty slot
+    fn parse_plugin(&mut self, block: &Block, index: u16) -> Result<Option<Plugin>, io::Error> {
+        const EMPTY_SLOT: u8 = 0xff;
+
+        self.set_position(block.offset + 2);
+        if self.read_u8()? == EMPTY_SLOT {
+            return Ok(None);
+        }
+
+        let (name, raw_name) = self.parse_name()?;
+        let manufacturer_id = self.parse_four_cc()?;
+        let product_id = self.parse_four_cc()?;
+        let plugin_id = self.parse_four_cc()?;
+        self.increment_position(7);
+        // Only present for AAX plugins
+        let bundle_id = if self.position() + 4 <= block.offset + block.size {
+            self.parse_str()?
+        } else {
+            String::new()
+        };
+        trace!("Found plugin {} ({})", name, bundle_id);
+
+        Ok(Some(Plugin {
+            name,
+            raw_name,
+            index,
+            manufacturer_id,
+            product_id,
+            plugin_id,
+            bundle_id,
+            tracks: vec![],
+        }))
+    }
+
+    fn parse_io(&mut self, block_map: &BlockMap) -> Result<(Vec<IoChannel>, Vec<IoRoute>), io::Error> {
+        let BlockMap { io_channel_blocks, io_route_blocks, .. } = block_map;
+        let mut io_channels = vec![];
+        let mut io_routes = vec![];
+
+        for b in filter_blocks!(io_channel_blocks.iter(), PTCD::IO_Channel_Entry) {
+            self.set_position(b.offset + 2);
+            let kind = self.read_u8()?.into();
+            let (name, raw_name) = self.parse_name_at(b.offset + 4)?;
+            let channels = self.parse_channel_list()?;
+            self.increment_position(7);
+            let id = self.parse_id()?.unwrap_or_default();
+            trace!("Found {:?} I/O channel {}", kind, name);
+
+            io_channels.push(IoChannel {
                 name,
-                sample_offset,
-                comment,
+                raw_name,
+                index: io_channels.len() as u16,
+                kind,
+                channels,
+                id,
             });
         }
-        
-        self.block_map = block_map;
-        Ok(markers)
+
+        for b in filter_blocks!(io_route_blocks.iter(), PTCD::IO_Route) {
+            let (name, raw_name) = self.parse_name_at(b.offset + 4)?;
+            let channels = self.parse_channel_list()?;
+            self.increment_position(8);
+            let id = self.parse_id()?.unwrap_or_default();
+
+            // Hardware ports the path is assigned to
+            let mut ports = vec![];
+            for port in filter_blocks!(children_of!(b, PTCD::IO_Route_Ports), PTCD::IO_Route_Port) {
+                self.set_position(port.offset + 14);
+                if let Some(port_id) = self.parse_id()? {
+                    if let Some(channel) = io_channels.iter().find(|c| c.id == port_id) {
+                        ports.push(channel.name.clone());
+                    }
+                }
+            }
+            trace!("Found I/O route {} -> {:?}", name, ports);
+
+            io_routes.push(IoRoute {
+                name,
+                raw_name,
+                index: io_routes.len() as u16,
+                channels,
+                ports,
+                id,
+            });
+        }
+
+        Ok((io_channels, io_routes))
+    }
+
+    // Routes the tracks and returns the plugins on their inserts, with the track names
+    fn parse_track_io<'a, T>(&mut self, block_map: &BlockMap, tracks: T, io_channels: &[IoChannel], io_routes: &[IoRoute]) -> Result<Vec<(String, Plugin)>, io::Error>
+        where T: Iterator<Item = &'a mut Track>
+    {
+        let BlockMap { track_list_blocks, track_io_blocks, .. } = block_map;
+
+        // Every track, audio and MIDI, is listed with its id. Track names are unique
+        // within a session, so the tracks take their id from the list by name.
+        let mut track_ids = vec![];
+        for b in filter_blocks!(track_list_blocks.iter(), PTCD::TRACK_Name_Number) {
+            let name = self.parse_str_at(b.offset + 4)?;
+            self.increment_position(6);
+            if let Some(id) = self.parse_id()? {
+                track_ids.push((id, name));
+            }
+        }
+        let mut tracks: Vec<&mut Track> = tracks.collect();
+        for track in tracks.iter_mut() {
+            if let Some((id, _)) = track_ids.iter().find(|(_, name)| *name == track.name) {
+                track.id = *id;
+            }
+        }
+
+        let mut inserts = vec![];
+        for entry in filter_blocks!(track_io_blocks.iter(), PTCD::TRACK_IO_Entry) {
+            let b = match children_of!(entry, PTCD::TRACK_IO).next() {
+                Some(b) => b,
+                None => continue,
+            };
+            // The track the entry is of, by the id its name block holds
+            let track_id = match b.find(PTCD::OBJECT_Name_Id).next() {
+                Some(name_id) => self.parse_name_id(name_id)?.1,
+                None => None,
+            };
+            let name = match track_ids.iter().find(|(id, _)| Some(*id) == track_id) {
+                Some((_, name)) => name.clone(),
+                None => continue,
+            };
+
+            let mut output = None;
+            if let Some(out) = b.find(PTCD::TRACK_Output).next() {
+                self.set_position(out.offset + 10);
+                let route_id = self.parse_id()?;
+                output = io_routes.iter().find(|r| Some(r.id) == route_id);
+            }
+
+            // Fields follow the output list: four flag bytes, a u16 counted list of u32
+            // ids, nine bytes that are not known, then the name and id of the physical
+            // input or bus, four bytes apart. The id is untagged when there is no input.
+            const FLAGS_SIZE: usize = 4;
+            const UNKNOWN_SIZE: usize = 9;
+            let mut input = None;
+            if let Some(outputs) = children_of!(b, PTCD::TRACK_Output_List).next() {
+                self.set_position(outputs.offset + outputs.size + FLAGS_SIZE);
+                let num_ids = self.read_u16()? as usize;
+                self.increment_position(num_ids * 4 + UNKNOWN_SIZE);
+                let input_name = self.parse_str()?;
+                self.increment_position(4);
+                if let Some(id) = self.parse_id()? {
+                    input = Some(match io_channels.iter().find(|c| c.id == id) {
+                        Some(channel) => channel.name.clone(),
+                        None => input_name,
+                    });
+                }
+            }
+
+            // Insert slots are plugin entries, 0xff for an empty slot as in the plugin list.
+            // None of the sessions at hand has an insert, so where in the entry the slots
+            // are is not known and the whole entry is searched.
+            for slot in entry.find(PTCD::PLUGIN_Entry) {
+                if let Some(plugin) = self.parse_plugin(slot, inserts.len() as u16)? {
+                    inserts.push((name.clone(), plugin));
+                }
+            }
+
+            trace!("Track {} routed {:?} -> {:?}", name, input, output.map(|r| &r.name));
+            for track in tracks.iter_mut().filter(|track| Some(track.id) == track_id) {
+                track.input = input.clone();
+                track.output = output.map(|r| r.name.clone());
+            }
+        }
+
+        Ok(inserts)
+    }
+
+    // The name in an OBJECT_Name_Id block and the id of what it names, such as a track
+    // or a ruler. Seven bytes that are not known lie in between.
+    fn parse_name_id(&mut self, block: &Block) -> Result<(String, Option<u64>), io::Error> {
+        let name = self.parse_str_at(block.offset + 2)?;
+        self.increment_position(7);
+        Ok((name, self.parse_id()?))
+    }
+
+    fn parse_channel_list(&mut self) -> Result<Vec<u16>, io::Error> {
+        let num_channels = self.read_u32()?;
+        (0..num_channels).map(|_| self.read_u16()).collect()
+    }
+
+    // Tracks and I/O paths are identified by tagged 8 byte ids
+    const ID_TAG: u32 = 0x2a;
+
+    fn parse_id(&mut self) -> Result<Option<u64>, io::Error> {
+        if self.read_u32()? != Self::ID_TAG {
+            return Ok(None);
+        }
+        Ok(Some(self.read_u64()? as u64))
+    }
+
+    // Four character codes are stored as integers, so they appear reversed in little endian sessions
+    fn parse_four_cc(&mut self) -> Result<String, io::Error> {
+        let code = self.read_u32()?.to_be_bytes();
+        Ok(String::from_utf8_lossy(&code).into_owned())
+    }
+}
+
+// Pushes the content types of `block` and its descendants around `offset`, false when
+// `block` does not hold it
+fn push_block_path(block: &Block, offset: usize, path: &mut Vec<u16>) -> bool {
+    let contains = |block: &Block| block.offset - 7 <= offset && offset < block.offset + block.size;
+    if !contains(block) {
+        return false;
+    }
+    let mut block = block;
+    loop {
+        path.push(block.content_type);
+        match block.children.iter().find(|child| contains(child)) {
+            Some(child) => block = child,
+            None => return true,
+        }
     }
 }
 
@@ -581,24 +1510,617 @@ impl PtSessionParser {
 mod test {
     use super::*;
     use std::fs::read_to_string;
+    use crate::encoding::Encoding;
     use serde_json as serde;
 
+    // Encodes a little endian block, `payload` follows the content type
+    fn block(content_type: PTCD, payload: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![0x5a, 0x01, 0x00];
+        bytes.extend_from_slice(&(payload.len() as u32 + 2).to_le_bytes());
+        bytes.extend_from_slice(&(content_type as u16).to_le_bytes());
+        bytes.extend_from_slice(payload);
+        bytes
+    }
+
+    fn string(s: &str) -> Vec<u8> {
+        legacy_string(s.as_bytes())
+    }
+
+    fn legacy_string(bytes: &[u8]) -> Vec<u8> {
+        let mut string = (bytes.len() as u32).to_le_bytes().to_vec();
+        string.extend_from_slice(bytes);
+        string
+    }
+
+    // A marker entry in the Pro Tools 12 layout, the fields that are not read left zero
+    fn marker_entry(index: u16, name: &[u8], sample_offset: u64, comment: &[u8]) -> Vec<u8> {
+        marker_entry_sized(index, name, sample_offset, comment, [59, 50, 39])
+    }
+
+    // A marker entry with `sizes` bytes of fields before, between and after the comment and its copy
+    fn marker_entry_sized(index: u16, name: &[u8], sample_offset: u64, comment: &[u8], sizes: [usize; 3]) -> Vec<u8> {
+        let mut entry = index.to_le_bytes().to_vec();
+        entry.extend_from_slice(&[0; 4]);
+        entry.extend(legacy_string(name));
+        entry.extend_from_slice(&sample_offset.to_le_bytes());
+        entry.extend_from_slice(&sample_offset.to_le_bytes());
+        entry.extend(vec![0xff; sizes[0]]);
+        entry.extend(legacy_string(comment));
+        entry.extend(vec![0x01; sizes[1]]);
+        entry.extend(legacy_string(comment));
+        entry.extend(vec![0; sizes[2]]);
+        block(PTCD::MARKER_List_Entry, &entry)
+    }
+
+    // Little endian three point value with four bytes per field
+    fn three_point(offset: u32, start: u32, len: u32) -> Vec<u8> {
+        let mut bytes = vec![0x00, 0x40, 0x40, 0x40, 0x00];
+        bytes.extend_from_slice(&offset.to_le_bytes());
+        bytes.extend_from_slice(&len.to_le_bytes());
+        bytes.extend_from_slice(&start.to_le_bytes());
+        bytes
+    }
+
+    // Builds an unencrypted (xor value 0) Pro Tools 12 session around `blocks`
+    fn synthetic_session(blocks: &[Vec<u8>]) -> Vec<u8> {
+        let mut bytes = vec![0u8; 0x14];
+        bytes[0] = 0x03;
+        bytes[0x12] = 0x05;
+        bytes.extend(block(PTCD::DUMMY, &[0; 2]));
+
+        // The version, document info fields and a path of only the file name
+        let mut version = vec![0u8; 18];
+        version.extend_from_slice(&10u32.to_le_bytes());
+        version.extend_from_slice(&[0; 5 * 4 + 24 + 4]);
+        version.extend(string("Session.ptx"));
+        bytes.extend(block(PTCD::INFO_Path_of_Session, &version));
+
+        let mut sample_rate = vec![0u8; 2];
+        sample_rate.extend_from_slice(&48000u32.to_le_bytes());
+        bytes.extend(block(PTCD::INFO_SampleRate, &sample_rate));
+
+        for b in blocks {
+            bytes.extend_from_slice(b);
+        }
+        bytes
+    }
+
     #[test]
     fn regions() {
         env_logger::init();
-        let session = PtSession::from("tests/RegionTest.ptx");
+        let session = PtSession::open("tests/RegionTest.ptx").unwrap();
         assert_eq!(session.version, 12);
         assert_eq!(session.session_sample_rate, 44100);
-        assert!(session.audio_files.len() > 0);
+        assert!(!session.audio_files.is_empty());
         assert_eq!(session.audio_files[0].file_name, "region_name_WAV.wav");
+        assert_eq!(session.audio_files[0].path, ["Macintosh HD", "Users", "elliottmalone", "Documents", "FadeTest", "Audio Files"]);
+        assert_eq!(session.session_path, ["Macintosh HD", "Users", "elliottmalone", "Documents", "FadeTest", "FadeTest.ptx"]);
+        assert_eq!(session.audio_regions[0].wav.as_ref().unwrap().file_name, "region_name_WAV.wav");
         assert_eq!(format!("{}", session), read_to_string("tests/RegionTestOutput.txt").unwrap());
+
+        let audio_2 = &session.audio_tracks[2];
+        assert_eq!(audio_2.format(), ChannelFormat::Stereo);
+        assert_eq!(audio_2.channels.iter().map(|c| c.index).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(audio_2.channels[1].regions[0].index, 1);
+        assert_eq!(session.channel_tracks().len(), 6);
+        assert_eq!(session.channel_tracks()[3].name, "Audio 2");
+    }
+
+    #[test]
+    fn resampled() {
+        let session = PtSession::open("tests/RegionTest.ptx").unwrap().resampled(48000);
+        assert_eq!(session.session_sample_rate, 48000);
+        assert_eq!(session.audio_files[0].len, 6432797);
+
+        let region = &session.audio_tracks[3].regions[0];
+        assert_eq!(region.start_pos, 0);
+        assert_eq!(region.sample_offset, 1034014);
+        assert_eq!(region.len, 1404082);
+        assert_eq!(session.audio_tracks[3].channels[1].regions[0], *region);
+
+        let same = PtSession::open("tests/RegionTest.ptx").unwrap();
+        assert_eq!(same.clone().resampled(44100), same);
+    }
+
+    #[test]
+    fn legacy_regions() {
+        // Generated by examples/legacy_session.rs, not saved by Pro Tools
+        let session = PtSession::open("tests/LegacyRegionTest.ptf").unwrap();
+        assert_eq!(session.version, 7);
+        assert_eq!(session.session_sample_rate, 44100);
+        let regions = &session.audio_tracks[0].regions;
+        assert_eq!(regions.len(), 2);
+        assert_eq!(regions[1].name, "Dialog-02");
+        assert_eq!(regions[1].start_pos, 441000);
+        assert_eq!(regions[1].wav.as_ref().unwrap().file_name, "Dialog.wav");
+        assert_eq!(format!("{}", session), read_to_string("tests/LegacyRegionTestOutput.txt").unwrap());
     }
 
     #[test]
     fn markers() {
-        let session = PtSession::from("tests/MarkerTest.ptx");
+        let session = PtSession::open("tests/MarkerTest.ptx").unwrap();
         assert_eq!(session.version, 12);
         assert_eq!(session.session_sample_rate, 48000);
-        assert_eq!(session.markers, serde::from_str::<Vec<Marker>>(&read_to_string("tests/MarkerTestOutput.json").unwrap()).unwrap())
+        assert_eq!(session.markers, serde::from_str::<Vec<Marker>>(&read_to_string("tests/MarkerTestOutput.json").unwrap()).unwrap());
+
+        // The first marker sits at 0x010101 and the last entry has an OBJECT_Name_Id child full of 0x01 bytes
+        let session = PtSession::open("tests/MarkerEdgeTest.ptx").unwrap();
+        assert_eq!(session.markers, serde::from_str::<Vec<Marker>>(&read_to_string("tests/MarkerEdgeTestOutput.json").unwrap()).unwrap());
+        let (_, diagnostics) = PtSession::from_bytes_lenient(&fs::read("tests/MarkerEdgeTest.ptx").unwrap()).unwrap();
+        assert!(diagnostics.iter().all(|d| d.section != Section::Markers));
+
+        // Other sizes of the fields around the comment, which are not read
+        let mut entries = marker_entry_sized(1, b"Cue", 0x0101, b"Fix \x01 sync", [12, 80, 4]);
+        entries.extend(marker_entry_sized(2, b"Empty", 96000, b"", [0, 0, 0]));
+        entries.extend(marker_entry_sized(3, b"Cue", 144000, b"Cue", [100, 1, 70]));
+        let bytes = synthetic_session(&[block(PTCD::MARKER_List, &block(PTCD::MARKER_List_Full, &entries))]);
+        let markers = PtSession::from_bytes(&bytes).unwrap().markers;
+        let fields: Vec<(&str, usize, &str)> = markers.iter()
+            .map(|m| (m.name.as_str(), m.sample_offset, m.comment.as_str()))
+            .collect();
+        assert_eq!(fields, [("Cue", 0x0101, "Fix \x01 sync"), ("Empty", 96000, ""), ("Cue", 144000, "Cue")]);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let bytes = std::fs::read("tests/MarkerTest.ptx").unwrap();
+        let session = PtSession::from_bytes(&bytes).unwrap();
+        assert_eq!(session, PtSession::open("tests/MarkerTest.ptx").unwrap());
+        assert_eq!(session, PtSession::from_reader(&bytes[..]).unwrap());
+    }
+
+    #[test]
+    fn invalid_input() {
+        assert!(matches!(PtSession::open("tests/DoesNotExist.ptx"), Err(PtError::Io(_))));
+        assert!(matches!(PtSession::from_bytes(&[0x03; 8]), Err(PtError::Decrypt(_))));
+        assert!(PtSession::from_bytes(&[0u8; 0x40]).is_err());
+    }
+
+    #[test]
+    fn parse_errors() {
+        // A file name that runs past the end of the file
+        let mut names = 1u32.to_le_bytes().to_vec();
+        names.extend_from_slice(&[2, 0xff, 0xff, 0xff, 0xff]);
+        names.extend_from_slice(&0xffffu32.to_le_bytes());
+        let mut wav_list = 1u32.to_le_bytes().to_vec();
+        wav_list.extend(block(PTCD::WAV_Names, &names));
+        let bytes = synthetic_session(&[block(PTCD::WAV_List_Full, &wav_list)]);
+
+        let names_offset = PtSessionParser::decrypt_bytes(&bytes).unwrap()
+            .find_blocks(PTCD::WAV_Names).unwrap()[0]
+            .offset;
+        match PtSession::from_bytes(&bytes) {
+            Err(PtError::Parse(err)) => {
+                assert_eq!(err.section, Section::AudioFiles);
+                assert_eq!(err.offset, Some(names_offset + 11));
+                assert_eq!(err.block_path, [PTCD::WAV_List_Full as u16, PTCD::WAV_Names as u16]);
+                assert!(std::error::Error::source(&err).is_some());
+                assert_eq!(
+                    err.to_string(),
+                    format!(
+                        "Error parsing audio files at byte {} in WAV_List_Full > WAV_Names: String of 65535 bytes runs past the end of the file",
+                        names_offset + 11
+                    )
+                );
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+
+        // An old version block whose application name runs past the end of the file
+        let mut bytes = vec![0u8; 0x14];
+        bytes[0] = 0x03;
+        bytes[0x12] = 0x05;
+        bytes.extend(block(PTCD::DUMMY, &[0; 2]));
+        let mut version = vec![0u8];
+        version.extend_from_slice(&0xffffu32.to_le_bytes());
+        bytes.extend(block(PTCD::INFO_Version, &version));
+        match PtSessionParser::decrypt_bytes(&bytes) {
+            Err(PtError::Parse(err)) => {
+                assert_eq!(err.section, Section::Header);
+                assert_eq!(err.block_path, [PTCD::INFO_Version as u16]);
+            }
+            Err(other) => panic!("Expected a parse error, got {:?}", other),
+            Ok(_) => panic!("Expected a parse error"),
+        }
+    }
+
+    #[test]
+    fn hostile() {
+        // Blocks nested far deeper than in any session
+        let mut nested = vec![0u8; 2];
+        for _ in 0..10_000 {
+            nested = block(PTCD::DUMMY, &nested);
+        }
+        assert!(PtSession::from_bytes(&synthetic_session(&[nested])).is_ok());
+
+        // A marker entry that ends before its comment
+        let mut entry = 2u16.to_le_bytes().to_vec();
+        entry.extend_from_slice(&[0; 4]);
+        entry.extend(string("A"));
+        entry.extend_from_slice(&48000u32.to_le_bytes());
+        let markers = block(PTCD::MARKER_List, &block(PTCD::MARKER_List_Full, &block(PTCD::MARKER_List_Entry, &entry)));
+        let bytes = synthetic_session(&[markers, block(PTCD::DUMMY, &[1; 8])]);
+        match PtSession::from_bytes(&bytes) {
+            Err(PtError::Parse(err)) => assert!(err.to_string().ends_with("Marker entry is truncated")),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+
+        // Version numbers that do not fit
+        let mut bytes = synthetic_session(&[]);
+        bytes[58..62].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(matches!(PtSessionParser::decrypt_bytes(&bytes), Err(PtError::Version(_))));
+    }
+
+    #[test]
+    fn lenient() {
+        // Break the sample rate block and add bytes that are not a block
+        let sample_rate = PtSessionParser::decrypt_bytes(&synthetic_session(&[])).unwrap()
+            .find_blocks(PTCD::INFO_SampleRate).unwrap()[0]
+            .offset - 7;
+        let mut bytes = synthetic_session(&[vec![0xff; 5], block(PTCD::MARKER_List, &[0; 4])]);
+        bytes[sample_rate] = 0;
+
+        assert!(matches!(PtSession::from_bytes(&bytes), Err(PtError::Parse(err)) if err.section == Section::Blocks));
+
+        let (session, diagnostics) = PtSession::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(session.session_sample_rate, 48000);
+        let found: Vec<(Section, Option<usize>)> = diagnostics.iter().map(|d| (d.section, d.offset)).collect();
+        assert_eq!(found, [(Section::Blocks, Some(sample_rate)), (Section::Header, None)]);
+        // Up to the marker block after the garbage
+        let skipped = bytes.len() - 13 - sample_rate;
+        assert!(diagnostics[0].message.starts_with(&format!("Skipped {} bytes", skipped)));
+        assert_eq!(diagnostics[1].message, "No sample rate block");
+
+        let mut parser = PtSessionParser::decrypt_bytes(&bytes).unwrap();
+        assert!(parser.blocks().is_err());
+        let (blocks, diagnostics) = parser.blocks_lenient().unwrap();
+        assert_eq!(blocks.last().unwrap().content_type, PTCD::MARKER_List as u16);
+        assert_eq!(diagnostics.len(), 1);
+
+        // A plugin entry with a name that runs past its block does not fail the session
+        let mut entry = vec![0u8; 1];
+        entry.extend_from_slice(&0xffffu32.to_le_bytes());
+        let bytes = synthetic_session(&[block(PTCD::PLUGIN_Full_List, &block(PTCD::PLUGIN_Entry, &entry))]);
+        assert!(matches!(PtSession::from_bytes(&bytes), Err(PtError::Parse(err)) if err.section == Section::Plugins));
+        let (_, diagnostics) = PtSession::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(diagnostics.iter().map(|d| d.section).collect::<Vec<_>>(), [Section::Plugins]);
+
+        // A marker entry truncated before the end of the marker only drops that marker,
+        // the session path only itself, and the sections after a failed one still parse
+        let mut entries = marker_entry(1, b"A", 48000, b"");
+        entries.truncate(entries.len() - 160);
+        let size = entries.len() as u32 - 7;
+        entries[3..7].copy_from_slice(&size.to_le_bytes());
+        entries.extend(marker_entry(2, b"B", 48000, b"Comment"));
+        let markers = block(PTCD::MARKER_List, &block(PTCD::MARKER_List_Full, &entries));
+        let mut names = 1u32.to_le_bytes().to_vec();
+        names.extend_from_slice(&[2, 0xff, 0xff, 0xff, 0xff]);
+        names.extend_from_slice(&0xffffu32.to_le_bytes());
+        let mut wav_list = 1u32.to_le_bytes().to_vec();
+        wav_list.extend(block(PTCD::WAV_Names, &names));
+        let wav_list = block(PTCD::WAV_List_Full, &wav_list);
+        let mut bytes = synthetic_session(&[wav_list, markers]);
+        let path = bytes.windows(11).position(|w| w == b"Session.ptx").unwrap() - 4;
+        bytes[path..path + 4].copy_from_slice(&0xffffu32.to_le_bytes());
+
+        let (session, diagnostics) = PtSession::from_bytes_lenient(&bytes).unwrap();
+        assert!(session.session_path.is_empty());
+        assert!(session.audio_files.is_empty());
+        assert_eq!(session.markers.len(), 1);
+        assert_eq!((session.markers[0].name.as_str(), session.markers[0].comment.as_str()), ("B", "Comment"));
+        let sections: Vec<Section> = diagnostics.iter().map(|d| d.section).collect();
+        assert_eq!(sections, [Section::Header, Section::AudioFiles, Section::Markers]);
+    }
+
+    #[test]
+    fn midi() {
+        // One chunk holding two notes, the first note sets the zero point
+        let mut events = b"MdNLB".to_vec();
+        events.extend_from_slice(&[0; 6]);
+        events.extend_from_slice(&2u32.to_le_bytes());
+        for &(pos, note, len, velocity) in &[(1000u64, 60u8, 480u64, 100u8), (1480, 64, 960, 80)] {
+            let mut event = vec![0u8; 35];
+            event[..5].copy_from_slice(&pos.to_le_bytes()[..5]);
+            event[8] = note;
+            event[9..14].copy_from_slice(&len.to_le_bytes()[..5]);
+            event[17] = velocity;
+            events.extend(event);
+        }
+
+        // Region info: name, three point (offset 0, len 1440, start 0), then the chunk index
+        let mut region_info = string("Notes");
+        region_info.extend_from_slice(&[0x00, 0x00, 0x20, 0x00, 0x00, 0xa0, 0x05]);
+        let mut region = block(PTCD::REGION_Name_Number, &region_info);
+        region.extend_from_slice(&0u32.to_le_bytes());
+        let regions = block(PTCD::MIDI_Regions_Map_v10, &block(PTCD::MIDI_Region_Name_Number_v10, &region));
+
+        let mut placement = vec![0u8; 2];
+        placement.extend_from_slice(&0u32.to_le_bytes());
+        placement.push(0);
+        placement.extend_from_slice(&48000u64.to_le_bytes()[..5]);
+        let mut track = string("MIDI 1");
+        track.extend(block(PTCD::MIDI_Region_Track_Entry, &block(PTCD::AUDIO_Region_Track_SubEntry_v8, &placement)));
+        let tracks = block(PTCD::MIDI_Region_Track_Full_Map, &block(PTCD::MIDI_Region_Track_Map_Entries, &track));
+
+        let bytes = synthetic_session(&[block(PTCD::MIDI_Events_Block, &events), regions, tracks]);
+        let session = PtSession::from_bytes(&bytes).unwrap();
+        assert_eq!(session.version, 12);
+        assert_eq!(session.midi_regions.len(), 1);
+        assert_eq!(session.midi_regions[0].name, "Notes");
+        assert_eq!(session.midi_regions[0].len, 1440);
+        assert_eq!(session.midi_regions[0].midi_events, vec![
+            MidiEvent { pos: 0, len: 480, note: 60, velocity: 100 },
+            MidiEvent { pos: 480, len: 960, note: 64, velocity: 80 },
+        ]);
+        assert_eq!(session.midi_tracks.len(), 1);
+        assert_eq!(session.midi_tracks[0].name, "MIDI 1");
+        assert_eq!(session.midi_tracks[0].regions[0].start_pos, 48000);
+    }
+
+    #[test]
+    fn region_groups() {
+        let mut regions = vec![];
+        for (name, offset, len) in &[("Dialog", 0, 48000), ("Room Tone", 1000, 24000)] {
+            let mut info = string(name);
+            info.extend(three_point(*offset, 0, *len));
+            let mut region = block(PTCD::COMPOUND_Region_Group, &info);
+            region.extend_from_slice(&0u32.to_le_bytes());
+            regions.extend(block(PTCD::AUDIO_Region_Name_Number_v10, &region));
+        }
+
+        // Both regions back to back, with the first 12000 samples of the group trimmed,
+        // and a member that is not a region
+        let mut group = string("Scene");
+        group.extend(three_point(12000, 0, 60000));
+        for (index, offset, start, len) in &[(0u32, 0, 0, 48000), (1, 1000, 48000, 24000), (7, 0, 72000, 100)] {
+            let mut element = index.to_le_bytes().to_vec();
+            element.extend(three_point(*offset, *start, *len));
+            group.extend(block(PTCD::COMPOUND_Region_element, &element));
+        }
+        let groups = block(PTCD::COMPOUND_Region_Full_Map, &block(PTCD::COMPOUND_Region_Group, &group));
+
+        let mut track = string("Audio 1");
+        track.push(0);
+        track.extend_from_slice(&1u32.to_le_bytes());
+        track.extend_from_slice(&0u16.to_le_bytes());
+        let tracks = block(PTCD::AUDIO_Tracks, &block(PTCD::AUDIO_Track_Name_Number, &track));
+
+        // The group is placed as region index 2, following the two regions
+        let mut placement = vec![0u8; 2];
+        placement.extend_from_slice(&2u32.to_le_bytes());
+        placement.extend_from_slice(&[0; 5]);
+        placement.extend_from_slice(&96000u32.to_le_bytes());
+        placement.extend_from_slice(&[0; 30]);
+        let mut entries = string("Audio 1");
+        entries.extend(block(PTCD::AUDIO_Region_Track_Entry_v8, &block(PTCD::AUDIO_Region_Track_SubEntry_v8, &placement)));
+        let map = block(PTCD::AUDIO_Region_Track_Full_Map_v8, &block(PTCD::AUDIO_Region_Track_Map_Entries_v8, &entries));
+
+        let bytes = synthetic_session(&[block(PTCD::AUDIO_Region_List_v10, &regions), groups, tracks.clone(), map.clone()]);
+        let session = PtSession::from_bytes(&bytes).unwrap();
+        assert_eq!(session.region_groups.len(), 1);
+        assert_eq!(session.region_groups[0].name, "Scene");
+        assert_eq!(session.region_groups[0].regions[1].name, "Room Tone");
+        assert_eq!(session.region_groups[0].regions[1].start_pos, 48000);
+        assert_eq!(session.region_groups[0].regions.len(), 2);
+        let (_, diagnostics) = PtSession::from_bytes_lenient(&bytes).unwrap();
+        // The test regions have no audio files either
+        assert_eq!(diagnostics.last().unwrap().message, "Region group `Scene` refers to unknown region 7");
+
+        // A group that does not parse fails strict parsing, lenient parsing places the
+        // tracks without the groups
+        let mut broken = 0xffffu32.to_le_bytes().to_vec();
+        broken.extend_from_slice(b"Scene");
+        let broken_groups = block(PTCD::COMPOUND_Region_Full_Map, &block(PTCD::COMPOUND_Region_Group, &broken));
+        let broken_bytes = synthetic_session(&[block(PTCD::AUDIO_Region_List_v10, &regions), broken_groups, tracks, map]);
+        assert!(PtSession::from_bytes(&broken_bytes).is_err());
+        let (broken_session, diagnostics) = PtSession::from_bytes_lenient(&broken_bytes).unwrap();
+        assert!(broken_session.region_groups.is_empty());
+        assert_eq!(broken_session.audio_tracks.len(), 1);
+        assert!(diagnostics.iter().any(|d| d.section == Section::AudioRegions));
+
+        let track = &session.audio_tracks[0];
+        assert!(track.regions.is_empty());
+        assert_eq!(track.groups[0].start_pos, 96000);
+        let placements: Vec<_> = track.flattened_regions()
+            .into_iter()
+            .map(|r| (r.name, r.start_pos, r.sample_offset, r.len))
+            .collect();
+        assert_eq!(placements, vec![
+            ("Dialog".to_string(), 96000, 12000, 36000),
+            ("Room Tone".to_string(), 132000, 1000, 24000),
+        ]);
+    }
+
+    #[test]
+    fn save() {
+        let original = std::fs::read("tests/MarkerTest.ptx").unwrap();
+        let mut parser = PtSessionParser::decrypt_bytes(&original).unwrap();
+        assert_eq!(parser.encrypt().unwrap(), original);
+
+        let pos = parser.unxored()
+            .windows(13)
+            .position(|window| window == b"ThisIsMarker2")
+            .unwrap();
+        parser.unxored_mut()[pos + 12] = b'X';
+
+        let path = temp_path("save");
+        parser.save(&path).unwrap();
+        let session = PtSession::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(session.markers[1].name, "ThisIsMarkerX");
+        assert_eq!(session.markers[0].name, "ThisIsMarker1");
+    }
+
+    #[test]
+    fn splice() {
+        let original = std::fs::read("tests/MarkerTest.ptx").unwrap();
+        let mut parser = PtSessionParser::decrypt_bytes(&original).unwrap();
+        let pos = parser.unxored()
+            .windows(13)
+            .position(|window| window == b"ThisIsMarker2")
+            .unwrap();
+        let mut name = 20u32.to_le_bytes().to_vec();
+        name.extend_from_slice(b"ThisIsAnotherMarker2");
+        parser.splice(pos - 4..pos + 13, &name).unwrap();
+        assert_eq!(parser.unxored().len(), original.len() + 7);
+        assert!(parser.splice(19..30, b"").is_err());
+
+        let path = temp_path("splice");
+        parser.save(&path).unwrap();
+        let session = PtSession::open(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(session.markers[1].name, "ThisIsAnotherMarker2");
+        assert_eq!(session.markers[1].sample_offset, 5668864);
+        assert_eq!(session.markers[2].name, "ThisIsMarker3");
+    }
+
+    // A file name in the temp directory no other test run uses
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("ptsession_{}_{}.ptx", name, std::process::id()))
+    }
+
+    #[test]
+    fn save_legacy() {
+        let original = std::fs::read("tests/LegacyRegionTest.ptf").unwrap();
+        let mut encrypted = vec![];
+        PtSessionParser::decrypt_bytes(&original).unwrap().write_to(&mut encrypted).unwrap();
+        assert_eq!(encrypted, original);
+    }
+
+    #[test]
+    fn blocks() {
+        let mut parser = PtSessionParser::decrypt("tests/RegionTest.ptx").unwrap();
+        assert_eq!(parser.blocks().unwrap()[0].content_type, 0x87f2);
+        assert_eq!(parser.find_blocks(PTCD::AUDIO_Region_Track_SubEntry_v8).unwrap().len(), 12);
+        assert_eq!(parser.find_blocks(0x1050u16).unwrap().len(), 12);
+
+        let full_map = &parser.find_blocks(PTCD::AUDIO_Region_Track_Full_Map_v8).unwrap()[0];
+        assert_eq!(full_map.ptcd(), Some(PTCD::AUDIO_Region_Track_Full_Map_v8));
+        assert_eq!(full_map.iter().nth(1).unwrap().ptcd(), Some(PTCD::AUDIO_Region_Track_Map_Entries_v8));
+        assert_eq!(full_map.find(PTCD::AUDIO_Region_Track_Entry_v8).count(), 12);
+
+        let sample_rates = parser.find_blocks(PTCD::INFO_SampleRate).unwrap();
+        assert_eq!(parser.block_data(&sample_rates[0])[2..6], 44100u32.to_le_bytes());
+    }
+
+    #[test]
+    fn legacy_names() {
+        // "Scène" with the comment "Café" and a plugin and input named "Entrée", as saved on a Mac
+        let marker = marker_entry(1, b"Sc\x8fne", 48000, b"Caf\x8e");
+        let markers = block(PTCD::MARKER_List, &block(PTCD::MARKER_List_Full, &marker));
+
+        let mut plugin = vec![0u8];
+        plugin.extend(legacy_string(b"Entr\x8ee"));
+        plugin.extend_from_slice(b"igiDPleFyloP");
+        let plugins = block(PTCD::PLUGIN_Full_List, &block(PTCD::PLUGIN_Entry, &plugin));
+
+        let mut channel = vec![0u8, 0];
+        channel.extend(legacy_string(b"Entr\x8ee"));
+        channel.extend_from_slice(&[0; 4 + 7 + 4]);
+        let io = block(PTCD::IO_Channel_List, &block(PTCD::IO_Channel_Entry, &channel));
+
+        // Pro Tools 9
+        let mut bytes = synthetic_session(&[markers, plugins, io]);
+        bytes[58..62].copy_from_slice(&7u32.to_le_bytes());
+        let session = PtSession::from_bytes(&bytes).unwrap();
+        assert_eq!(session.version, 9);
+
+        let marker = &session.markers[0];
+        assert_eq!((marker.name.as_str(), marker.comment.as_str()), ("Scène", "Café"));
+        assert_eq!(marker.raw_name.as_ref().unwrap().encoding, Encoding::MacRoman);
+        assert_eq!(marker.raw_comment.as_ref().unwrap().bytes, b"Caf\x8e");
+        assert_eq!(session.plugins[0].name, "Entrée");
+        assert_eq!(session.plugins[0].raw_name.as_ref().unwrap().bytes, b"Entr\x8ee");
+        assert_eq!(session.io_channels[0].name, "Entrée");
+        assert_eq!(session.io_channels[0].raw_name.as_ref().unwrap().encoding, Encoding::MacRoman);
+
+        let (_, diagnostics) = PtSession::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert!(diagnostics[0].message.ends_with("read with the Pro Tools 10 layout"));
+    }
+
+    #[test]
+    fn plugins() {
+        let session = PtSession::open("tests/RegionTest.ptx").unwrap();
+        assert_eq!(session.plugins, vec![Plugin {
+            name: "Polyphonic".into(),
+            raw_name: None,
+            index: 0,
+            manufacturer_id: "Digi".into(),
+            product_id: "FelP".into(),
+            plugin_id: "Poly".into(),
+            bundle_id: "com.avid.aax.fela.2sola".into(),
+            tracks: vec![],
+        }]);
+    }
+
+    #[test]
+    fn inserts() {
+        let mut plugin = vec![4u8];
+        plugin.extend(string("EQ3 7-Band"));
+        for four_cc in &[b"Digi", b"EQ3 ", b"EQ37"] {
+            plugin.extend_from_slice(&u32::from_be_bytes(**four_cc).to_le_bytes());
+        }
+        plugin.extend_from_slice(&[0; 7]);
+        let plugins = block(PTCD::PLUGIN_Full_List, &block(PTCD::PLUGIN_Entry, &plugin));
+
+        let id = |id: u64| {
+            let mut bytes = 0x2au32.to_le_bytes().to_vec();
+            bytes.extend_from_slice(&id.to_le_bytes());
+            bytes
+        };
+        let mut track_list = vec![];
+        let mut track_io = vec![];
+        for (name, track_id) in &[("Gtr", 7), ("Vox", 8)] {
+            let mut entry = vec![0; 2];
+            entry.extend(string(name));
+            entry.extend_from_slice(&[0; 6]);
+            entry.extend(id(*track_id));
+            track_list.extend(block(PTCD::TRACK_Name_Number, &entry));
+
+            let mut metadata = string(name);
+            metadata.extend_from_slice(&[0; 7]);
+            metadata.extend(id(*track_id));
+            let mut io = block(PTCD::OBJECT_Name_Id, &metadata);
+            // An empty slot, then the plugin on Vox only
+            io.extend(block(PTCD::PLUGIN_Entry, &[0xff; 4]));
+            if *name == "Vox" {
+                io.extend(block(PTCD::PLUGIN_Entry, &plugin));
+            }
+            track_io.extend(block(PTCD::TRACK_IO_Entry, &block(PTCD::TRACK_IO, &io)));
+        }
+        let bytes = synthetic_session(&[
+            block(PTCD::TRACK_Full_List, &track_list),
+            block(PTCD::TRACK_IO_List, &track_io),
+            plugins,
+        ]);
+
+        let session = PtSession::from_bytes(&bytes).unwrap();
+        assert_eq!(session.plugins.len(), 1);
+        assert_eq!(session.plugins[0].product_id, "EQ3 ");
+        assert_eq!(session.plugins[0].tracks, vec!["Vox"]);
+    }
+
+    #[test]
+    fn io() {
+        let session = PtSession::open("tests/RegionTest.ptx").unwrap();
+        assert_eq!(session.io_channels.len(), 48);
+        assert_eq!(session.io_channels[0].name, "MacBook Pro Microphone 1");
+        assert_eq!(session.io_channels[0].kind, IoKind::Input);
+        assert_eq!(session.io_channels[1].kind, IoKind::Output);
+        assert_eq!(session.io_channels[1].channels, vec![1, 2]);
+        assert_eq!(session.io_channels[2].name, "Bus 1-2");
+        assert_eq!(session.io_channels[2].kind, IoKind::Bus);
+
+        let monitors = &session.io_routes[0];
+        assert_eq!(monitors.name, "Monitors");
+        assert_eq!(monitors.channels, vec![1, 2]);
+        assert_eq!(monitors.ports, vec!["MacBook Pro Speakers 1-2"]);
+
+        let audio_1 = session.audio_tracks.iter().find(|t| t.name == "Audio 1").unwrap();
+        assert_eq!(audio_1.id.to_le_bytes(), [0x4c, 0xf3, 0x4c, 0xd9, 0x5e, 0xe1, 0x73, 0x9e]);
+        assert_eq!(audio_1.input.as_deref(), Some("MacBook Pro Microphone 1"));
+        assert_eq!(audio_1.output.as_deref(), Some("Monitors"));
+        assert!(session.audio_tracks.iter().filter(|t| t.name != "Audio 1").all(|t| t.input.is_none()));
     }
 }
@@ -15,9 +15,9 @@ macro_rules! read_endian {
                 0
             };
             let mut places: [u8; NUM_BYTES] = [0; NUM_BYTES];
-            self.read_exact(&mut places)?;
+            self.read_at_position(&mut places)?;
             for i in 0..NUM_BYTES {
-                val = (places[i] as $ret << limit) | val;
+                val |= (places[i] as $ret) << limit;
                 if self.is_bigendian() {
                     limit -= 8;
                 } else {
@@ -32,10 +32,20 @@ macro_rules! read_endian {
 pub trait ReadExt: Read + Seek + Endianness {
     fn read_u8(&mut self) -> Result<u8, io::Error> {
         let mut buf = [0; 1];
-        self.read_exact(&mut buf)?;
+        self.read_at_position(&mut buf)?;
         Ok(buf[0])
     }
 
+    // Like read_exact, but a failed read stays where it started so errors report that position
+    fn read_at_position(&mut self, buf: &mut [u8]) -> Result<(), io::Error> {
+        let pos = self.stream_position()?;
+        let result = self.read_exact(buf);
+        if result.is_err() {
+            self.seek(SeekFrom::Start(pos))?;
+        }
+        result
+    }
+
     read_endian!(read_u16, 16, u16);
     read_endian!(read_u24, 24, u32);
     read_endian!(read_u32, 32, u32);
@@ -54,7 +64,7 @@ pub trait ReadExt: Read + Seek + Endianness {
     }
 
     fn parse_three_point(&mut self) -> Result<(usize, usize, usize), io::Error> {
-        let pos = self.seek(SeekFrom::Current(0))?;
+        let pos = self.stream_position()?;
 
         let (offset_bytes, len_bytes, start_bytes) = if self.is_bigendian() {
             self.seek(SeekFrom::Current(2))?;
@@ -1,26 +1,176 @@
+use crate::{
+    content_description::PTCD,
+    encoding::RawString,
+    error::{Diagnostic, PtError},
+    parser::PtSessionParser,
+    timecode::{Timeline, TimelineError, TimecodeRate},
+};
+
 use serde::{Serialize, Deserialize};
 
+use std::convert::TryInto;
+use std::io::Read;
 use std::path::Path;
 use std::fmt;
 
-#[derive(Default, Debug, PartialEq)]
+#[derive(Default, Debug, Clone, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct PtSession {
     pub session_sample_rate: u64,
+    /// Not read from session files, which store it in a way that is not known yet.
+    /// Known for sessions parsed from a session info report, otherwise set by the caller.
+    pub timecode_rate: Option<TimecodeRate>,
+    /// Session start in samples, known like `timecode_rate`
+    pub session_start: Option<u64>,
+    /// Volume, folders and file name the session was last saved as.
+    /// Empty for sessions before Pro Tools 10.
+    pub session_path: Vec<String>,
     pub version: u8,
     pub num_blocks: usize,
     pub audio_files: Vec<Wav>,
     pub audio_regions: Vec<Region>,
     pub audio_tracks: Vec<Track>,
+    pub region_groups: Vec<RegionGroup>,
+    pub midi_regions: Vec<Region>,
+    pub midi_tracks: Vec<Track>,
     pub markers: Vec<Marker>,
+    pub plugins: Vec<Plugin>,
+    pub io_channels: Vec<IoChannel>,
+    pub io_routes: Vec<IoRoute>,
 }
 
-impl<P: AsRef<Path>> From<P> for PtSession {
-    fn from(path: P) -> Self {
-        crate::parser::PtSessionParser::decrypt(path)
-            .expect("Successful decrypt")
-            .parse_session()
-            .expect("Successful parse")
+impl PtSession {
+    /// Decrypts and parses the Pro Tools session file at `path`.
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, PtError> {
+        PtSessionParser::decrypt(path)?.parse_session()
+    }
+
+    /// Decrypts and parses a Pro Tools session held in memory.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PtError> {
+        PtSessionParser::decrypt_bytes(bytes)?.parse_session()
+    }
+
+    /// Decrypts and parses a Pro Tools session read from `reader`.
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, PtError> {
+        PtSessionParser::decrypt_reader(reader)?.parse_session()
+    }
+
+    /// Decrypts and parses as much as possible of the session file at `path`, along with
+    /// what could not be parsed, see [`PtSessionParser::parse_session_lenient`].
+    pub fn open_lenient<P: AsRef<Path>>(path: P) -> Result<(Self, Vec<Diagnostic>), PtError> {
+        PtSessionParser::decrypt(path)?.parse_session_lenient()
+    }
+
+    /// Lenient parsing of a session held in memory, see [`PtSession::open_lenient`].
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<(Self, Vec<Diagnostic>), PtError> {
+        PtSessionParser::decrypt_bytes(bytes)?.parse_session_lenient()
+    }
+
+    /// Returns the session conformed to `target_sample_rate`, see [`PtSession::resample`].
+    pub fn resampled(mut self, target_sample_rate: u64) -> Self {
+        self.resample(target_sample_rate);
+        self
+    }
+
+    /// Rescales every sample position and length in the session to `target_sample_rate`.
+    ///
+    /// Positions are rounded to the nearest sample, halves rounding up. Region lengths are
+    /// derived from their rounded end so that regions which touch keep touching.
+    /// MIDI regions and events are positioned in ticks and are left alone.
+    pub fn resample(&mut self, target_sample_rate: u64) {
+        let rate = Resample {
+            from: self.session_sample_rate,
+            to: target_sample_rate,
+        };
+        if rate.from == 0 || rate.from == rate.to {
+            self.session_sample_rate = target_sample_rate;
+            return;
+        }
+
+        for wav in &mut self.audio_files {
+            rate.wav(wav);
+        }
+        for region in &mut self.audio_regions {
+            rate.region(region);
+        }
+        for group in &mut self.region_groups {
+            rate.group(group);
+        }
+        for track in &mut self.audio_tracks {
+            for region in &mut track.regions {
+                rate.region(region);
+            }
+            for group in &mut track.groups {
+                rate.group(group);
+            }
+            for channel in &mut track.channels {
+                for region in &mut channel.regions {
+                    rate.region(region);
+                }
+                for group in &mut channel.groups {
+                    rate.group(group);
+                }
+            }
+        }
+        for marker in &mut self.markers {
+            marker.sample_offset = rate.sample(marker.sample_offset as u64) as usize;
+        }
+        self.session_start = self.session_start.map(|start| rate.sample(start));
+
+        self.session_sample_rate = target_sample_rate;
+    }
+
+    /// Converts sample positions of this session into timecode and other time formats.
+    /// Needs the timecode rate and session start, which session files do not provide.
+    pub fn timeline(&self) -> Result<Timeline, TimelineError> {
+        Timeline::new(
+            self.session_sample_rate,
+            self.timecode_rate.ok_or(TimelineError::NoTimecodeRate)?,
+            self.session_start.ok_or(TimelineError::NoSessionStart)?,
+        )
+    }
+
+    /// Audio tracks split into one mono track per channel, as they are stored in the session.
+    pub fn channel_tracks(&self) -> Vec<Track> {
+        self.audio_tracks.iter().flat_map(Track::split_channels).collect()
+    }
+}
+
+struct Resample {
+    from: u64,
+    to: u64,
+}
+
+impl Resample {
+    fn sample(&self, pos: u64) -> u64 {
+        ((pos as u128 * self.to as u128 + self.from as u128 / 2) / self.from as u128) as u64
+    }
+
+    fn len(&self, pos: u64, len: u64) -> u64 {
+        self.sample(pos + len) - self.sample(pos)
+    }
+
+    fn wav(&self, wav: &mut Wav) {
+        wav.len = self.len(wav.pos_absolute as u64, wav.len as u64) as usize;
+        wav.pos_absolute = self.sample(wav.pos_absolute as u64) as usize;
+    }
+
+    fn region(&self, region: &mut Region) {
+        region.len = self.len(region.start_pos, region.len as u64) as usize;
+        region.start_pos = self.sample(region.start_pos);
+        region.sample_offset = self.sample(region.sample_offset);
+        if let Some(wav) = &mut region.wav {
+            self.wav(wav);
+        }
+    }
+
+    fn group(&self, group: &mut RegionGroup) {
+        group.len = self.len(group.start_pos, group.len as u64) as usize;
+        group.start_pos = self.sample(group.start_pos);
+        group.sample_offset = self.sample(group.sample_offset);
+        for region in &mut group.regions {
+            self.region(region);
+        }
     }
 }
 
@@ -31,7 +181,6 @@ impl fmt::Display for PtSession {
             "Pro Tools {} Session: Samplerate = {}",
             self.version, self.session_sample_rate
         )?;
-        //writeln!(f, "Target samplerate = {}\n", self.target_sample_rate)?;
         writeln!(
             f,
             "{} wavs, {} regions\n",
@@ -59,7 +208,7 @@ impl fmt::Display for PtSession {
                     "`{}`, r({}), w({}), @ {}, {}",
                     r.name,
                     r.index,
-                    r.wav.as_ref().unwrap().index,
+                    r.wav.as_ref().map_or("-".to_string(), |wav| wav.index.to_string()),
                     r.sample_offset,
                     r.len
                 )?;
@@ -85,11 +234,41 @@ impl fmt::Display for PtSession {
             writeln!(f)?;
         }
 
+        if !self.midi_regions.is_empty() {
+            writeln!(f, "MIDI Region (Region#) @ into-sample, length:")?;
+            for r in &self.midi_regions {
+                writeln!(
+                    f,
+                    "`{}`, r({}), @ {}, {}, {} events",
+                    r.name,
+                    r.index,
+                    r.sample_offset,
+                    r.len,
+                    r.midi_events.len()
+                )?;
+            }
+            writeln!(f)?;
+        }
+
+        if !self.midi_tracks.is_empty() {
+            writeln!(f, "MIDI Track name (Track#) (Region#) @ Absolute:")?;
+            for t in &self.midi_tracks {
+                if !t.regions.is_empty() {
+                    write!(f, "`{}` t({})", t.name, t.index)?;
+                    for region in &t.regions {
+                        write!(f, " r({}) @ {}", region.index, region.start_pos)?;
+                    }
+                    writeln!(f)?;
+                }
+            }
+            writeln!(f)?;
+        }
+
         Ok(())
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct Block {
     pub z_mark: u8,
@@ -100,10 +279,55 @@ pub struct Block {
     pub children: Vec<Block>,
 }
 
+impl Block {
+    /// The known content type of this block.
+    pub fn ptcd(&self) -> Option<PTCD> {
+        self.content_type.try_into().ok()
+    }
+
+    /// Iterates over this block and all of its descendants, depth first.
+    pub fn iter(&self) -> Blocks<'_> {
+        Blocks { stack: vec![self] }
+    }
+
+    /// This block and its descendants of `content_type`, depth first.
+    pub fn find<T: Into<u16>>(&self, content_type: T) -> impl Iterator<Item = &Block> {
+        let content_type = content_type.into();
+        self.iter().filter(move |block| block.content_type == content_type)
+    }
+
+    /// The bytes following the content type, up to the end of the block, within
+    /// the decrypted session `unxored`.
+    pub fn payload<'a>(&self, unxored: &'a [u8]) -> &'a [u8] {
+        let end = (self.offset + self.size).min(unxored.len());
+        unxored.get(self.offset + 2..end).unwrap_or(&[])
+    }
+}
+
+/// Depth first iterator over a block tree, see [`Block::iter`].
+pub struct Blocks<'a> {
+    stack: Vec<&'a Block>,
+}
+
+impl<'a> Iterator for Blocks<'a> {
+    type Item = &'a Block;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let block = self.stack.pop()?;
+        self.stack.extend(block.children.iter().rev());
+        Some(block)
+    }
+}
+
 #[derive(Default, Debug, Clone, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct Wav {
     pub file_name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
+    /// Volume and folders the file was in when it was added to the session, empty when unknown
+    pub path: Vec<String>,
     pub index: u16,
     pub pos_absolute: usize,
     pub len: usize,
@@ -113,28 +337,271 @@ pub struct Wav {
 #[derive(Serialize, Deserialize)]
 pub struct Region {
     pub name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
     pub index: u16,
     pub start_pos: u64,
     pub sample_offset: u64,
     pub len: usize,
     pub wav: Option<Wav>,
+    pub midi_events: Vec<MidiEvent>,
+}
+
+/// A group of regions (compound region) that is placed on a track as a whole.
+/// Member regions are positioned relative to the start of the group's contents.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct RegionGroup {
+    pub name: String,
+    pub index: u16,
+    pub start_pos: u64,
+    pub sample_offset: u64,
+    pub len: usize,
+    pub regions: Vec<Region>,
+}
+
+impl RegionGroup {
+    /// Member regions placed on the absolute timeline, trimmed to the visible part of the group.
+    pub fn flatten(&self) -> Vec<Region> {
+        let visible_start = self.sample_offset;
+        let visible_end = self.sample_offset + self.len as u64;
+
+        self.regions
+            .iter()
+            .filter_map(|region| {
+                let start = region.start_pos.max(visible_start);
+                let end = (region.start_pos + region.len as u64).min(visible_end);
+                if start >= end {
+                    return None;
+                }
+
+                Some(Region {
+                    start_pos: self.start_pos + (start - visible_start),
+                    sample_offset: region.sample_offset + (start - region.start_pos),
+                    len: (end - start) as usize,
+                    ..region.clone()
+                })
+            })
+            .collect()
+    }
+}
+
+/// A MIDI note. Positions and lengths are in MIDI ticks.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct MidiEvent {
+    pub pos: u64,
+    pub len: u64,
+    pub note: u8,
+    pub velocity: u8,
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct Track {
     pub name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
+    /// Index of the track's first channel
     pub index: u16,
+    /// Id of the track in the session's track list, 0 when it is not listed
+    #[serde(default)]
+    pub id: u64,
     pub playlist: u8,
+    /// Regions of the track's first channel
     pub regions: Vec<Region>,
+    /// Region groups of the track's first channel
+    pub groups: Vec<RegionGroup>,
+    pub channels: Vec<Channel>,
+    pub input: Option<String>,
+    pub output: Option<String>,
+}
+
+impl Track {
+    pub fn format(&self) -> ChannelFormat {
+        ChannelFormat::from(self.channels.len())
+    }
+
+    /// Regions of the track's first channel with its region groups flattened, ordered by start.
+    pub fn flattened_regions(&self) -> Vec<Region> {
+        let mut regions = self.regions.clone();
+        regions.extend(self.groups.iter().flat_map(RegionGroup::flatten));
+        regions.sort_by_key(|region| region.start_pos);
+        regions
+    }
+
+    /// Regions of every channel with region groups flattened, ordered by start.
+    /// A track without channels has its regions as the first channel.
+    pub fn channel_regions(&self) -> Vec<Vec<Region>> {
+        if self.channels.is_empty() {
+            vec![self.flattened_regions()]
+        } else {
+            self.split_channels().iter().map(Track::flattened_regions).collect()
+        }
+    }
+
+    /// One mono track per channel, each named after this track.
+    pub fn split_channels(&self) -> Vec<Track> {
+        self.channels
+            .iter()
+            .map(|channel| Track {
+                index: channel.index,
+                regions: channel.regions.clone(),
+                groups: channel.groups.clone(),
+                channels: vec![channel.clone()],
+                ..self.clone()
+            })
+            .collect()
+    }
+}
+
+/// A single channel of a track and the regions placed on it.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Channel {
+    pub index: u16,
+    pub regions: Vec<Region>,
+    pub groups: Vec<RegionGroup>,
+}
+
+/// Channel format of a track, as far as it follows from the channel count.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum ChannelFormat {
+    Mono,
+    Stereo,
+    Lcr,
+    Quad,
+    Surround50,
+    Surround51,
+    Surround61,
+    Surround71,
+    Other(usize),
+}
+
+impl From<usize> for ChannelFormat {
+    fn from(num_channels: usize) -> Self {
+        match num_channels {
+            1 => ChannelFormat::Mono,
+            2 => ChannelFormat::Stereo,
+            3 => ChannelFormat::Lcr,
+            4 => ChannelFormat::Quad,
+            5 => ChannelFormat::Surround50,
+            6 => ChannelFormat::Surround51,
+            7 => ChannelFormat::Surround61,
+            8 => ChannelFormat::Surround71,
+            n => ChannelFormat::Other(n),
+        }
+    }
+}
+
+impl fmt::Display for ChannelFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelFormat::Mono => write!(f, "Mono"),
+            ChannelFormat::Stereo => write!(f, "Stereo"),
+            ChannelFormat::Lcr => write!(f, "LCR"),
+            ChannelFormat::Quad => write!(f, "Quad"),
+            ChannelFormat::Surround50 => write!(f, "5.0"),
+            ChannelFormat::Surround51 => write!(f, "5.1"),
+            ChannelFormat::Surround61 => write!(f, "6.1"),
+            ChannelFormat::Surround71 => write!(f, "7.1"),
+            ChannelFormat::Other(n) => write!(f, "{} channels", n),
+        }
+    }
 }
 
 #[derive(Default, Debug, Clone, PartialEq)]
 #[derive(Serialize, Deserialize)]
 pub struct Marker {
     pub name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
     pub index: u16,
     pub comment: String,
+    /// The comment as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_comment: Option<RawString>,
     pub sample_offset: usize,
+    /// Name of the marker ruler the marker is on
+    #[serde(default)]
+    pub ruler: String,
+}
+
+/// A plugin the session depends on. Ids are four character codes.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct Plugin {
+    pub name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
+    pub index: u16,
+    pub manufacturer_id: String,
+    pub product_id: String,
+    pub plugin_id: String,
+    pub bundle_id: String,
+    /// Names of the tracks with the plugin on an insert
+    #[serde(default)]
+    pub tracks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub enum IoKind {
+    Input,
+    Output,
+    Bus,
+    Insert,
+    Unknown(u8),
+}
+
+impl Default for IoKind {
+    fn default() -> Self {
+        IoKind::Unknown(0xff)
+    }
+}
+
+impl From<u8> for IoKind {
+    fn from(kind: u8) -> Self {
+        match kind {
+            0 => IoKind::Input,
+            1 => IoKind::Output,
+            2 => IoKind::Bus,
+            3 => IoKind::Insert,
+            _ => IoKind::Unknown(kind),
+        }
+    }
 }
 
+/// A hardware input, output or insert, or an internal bus, of the session's I/O setup.
+/// Channels are numbered from 1.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct IoChannel {
+    pub name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
+    pub index: u16,
+    pub kind: IoKind,
+    pub channels: Vec<u16>,
+    pub id: u64,
+}
+
+/// An output or bus path, with the names of the hardware ports it is assigned to.
+#[derive(Default, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize)]
+pub struct IoRoute {
+    pub name: String,
+    /// The name as stored, when it was not UTF-8
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub raw_name: Option<RawString>,
+    pub index: u16,
+    pub channels: Vec<u16>,
+    pub ports: Vec<String>,
+    pub id: u64,
+}
@@ -0,0 +1,522 @@
+//! The text report written by Pro Tools' "Export Session Info as Text".
+//!
+//! Sample times count from the session start, like positions in [`PtSession`].
+//! The report has no region offsets into their audio files, nor file lengths.
+
+use crate::{
+    error::{self, ParseError, PtError},
+    export,
+    session::{Channel, Marker, PtSession, Region, Track, Wav},
+    timecode::{Timecode, TimecodeRate, Timeline},
+};
+
+use std::io::{self, Write};
+
+/// Time format of the event and marker columns when writing a report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimeFormat {
+    Samples,
+    Timecode,
+    MinSecs,
+}
+
+impl Default for TimeFormat {
+    fn default() -> Self {
+        TimeFormat::Samples
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Section {
+    Header,
+    Files,
+    Clips,
+    Tracks,
+    Markers,
+    Other,
+}
+
+impl Section {
+    fn session_section(self) -> error::Section {
+        match self {
+            Section::Header | Section::Other => error::Section::Header,
+            Section::Files => error::Section::AudioFiles,
+            Section::Clips => error::Section::AudioRegions,
+            Section::Tracks => error::Section::AudioTracks,
+            Section::Markers => error::Section::Markers,
+        }
+    }
+}
+
+// Errors of a line of the report, which parse places
+type LineResult<T> = Result<T, &'static str>;
+
+// Channels of the widest Pro Tools track format, 9.1.6 or third order Ambisonics
+const MAX_CHANNELS: usize = 16;
+
+/// Parses a session info text report into a session.
+/// Errors give the byte offset of the line that could not be parsed.
+pub fn parse(text: &str) -> Result<PtSession, PtError> {
+    let mut parser = Parser {
+        session: PtSession::default(),
+        section: Section::Header,
+        columns: vec![],
+        next_channel: 0,
+        start: None,
+    };
+
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        parser.line(line.trim_end_matches(['\r', '\n'])).map_err(|message| {
+            ParseError::new(parser.section.session_section(), message).at(offset)
+        })?;
+        offset += line.len();
+    }
+
+    let mut session = parser.session;
+    for track in &mut session.audio_tracks {
+        if let Some(first) = track.channels.first() {
+            track.index = first.index;
+            track.regions = first.regions.clone();
+        }
+    }
+    Ok(session)
+}
+
+struct Parser<'a> {
+    session: PtSession,
+    section: Section,
+    columns: Vec<String>,
+    next_channel: u16,
+    start: Option<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    fn line(&mut self, line: &'a str) -> LineResult<()> {
+        let session = &mut self.session;
+        if line.trim().is_empty() {
+            return Ok(());
+        }
+
+        if let Some(next) = section_of(line) {
+            // The start timecode comes before its format
+            if let (Section::Header, Some(start)) = (self.section, self.start) {
+                let rate = session.timecode_rate.ok_or("Session start without a timecode format")?;
+                let timeline = Timeline::new(session.session_sample_rate, rate, 0)
+                    .map_err(|_| "Session start without a sample rate")?;
+                let start = Timecode::parse(start, rate)
+                    .and_then(|start| timeline.position(&start))
+                    .ok_or("Invalid session start timecode")?;
+                session.session_start = Some(start);
+            }
+            self.section = next;
+            self.columns.clear();
+            return Ok(());
+        }
+
+        let fields: Vec<&str> = line.split('\t').map(str::trim).collect();
+        match self.section {
+            Section::Header => parse_header(session, &mut self.start, &fields)?,
+            Section::Files => {
+                if fields[0] != "Filename" {
+                    session.audio_files.push(Wav {
+                        file_name: fields[0].to_string(),
+                        index: session.audio_files.len() as u16,
+                        ..Default::default()
+                    });
+                }
+            }
+            Section::Clips => {
+                if fields[0] != "CLIP NAME" && fields[0] != "REGION NAME" {
+                    let wav = fields.get(1).and_then(|file_name| {
+                        session.audio_files.iter().find(|wav| wav.file_name == *file_name).cloned()
+                    });
+                    session.audio_regions.push(Region {
+                        name: fields[0].to_string(),
+                        index: session.audio_regions.len() as u16,
+                        wav,
+                        ..Default::default()
+                    });
+                }
+            }
+            Section::Tracks => match fields[0] {
+                "TRACK NAME:" => {
+                    self.columns.clear();
+                    session.audio_tracks.push(Track {
+                        name: fields.get(1).unwrap_or(&"").to_string(),
+                        ..Default::default()
+                    });
+                }
+                "CHANNEL" => self.columns = fields.iter().map(|f| f.to_string()).collect(),
+                _ if self.columns.is_empty() => {}
+                _ => {
+                    let event = Event::parse(&self.columns, &fields, session)?;
+                    let region = session.audio_regions
+                        .iter()
+                        .find(|region| region.name == event.name)
+                        .cloned()
+                        .unwrap_or_else(|| Region { name: event.name.clone(), ..Default::default() });
+                    let track = session.audio_tracks.last_mut().ok_or("Event before the first track")?;
+
+                    while track.channels.len() < event.channel {
+                        track.channels.push(Channel { index: self.next_channel, ..Default::default() });
+                        self.next_channel = self.next_channel.checked_add(1).ok_or("Too many channels")?;
+                    }
+                    track.channels[event.channel - 1].regions.push(Region {
+                        start_pos: event.start,
+                        len: event.len as usize,
+                        ..region
+                    });
+                }
+            },
+            Section::Markers => {
+                if fields[0] == "#" {
+                    self.columns = fields.iter().map(|f| f.to_string()).collect();
+                } else if !self.columns.is_empty() {
+                    let marker = parse_marker(&self.columns, &fields, session)?;
+                    session.markers.push(marker);
+                }
+            }
+            Section::Other => {}
+        }
+        Ok(())
+    }
+}
+
+/// Writes the session info text report of `session`, named `name`.
+/// Fails with `InvalidInput` when `format` is timecode or min:secs and the timecode rate
+/// or start of the session is not known. Reports in samples are written without them,
+/// leaving the session start and timecode format out of the header.
+pub fn write<W: Write>(out: &mut W, session: &PtSession, name: &str, format: TimeFormat) -> io::Result<()> {
+    let timeline = match session.timeline() {
+        Ok(timeline) => Some(timeline),
+        Err(_) if format == TimeFormat::Samples => None,
+        Err(err) => return Err(err.into()),
+    };
+    let time = |pos: u64| match (format, &timeline) {
+        (TimeFormat::Timecode, Some(timeline)) => timeline.timecode(pos).to_string(),
+        (TimeFormat::MinSecs, Some(timeline)) => timeline.min_sec(pos),
+        _ => pos.to_string(),
+    };
+
+    writeln!(out, "SESSION NAME:\t{}", name)?;
+    writeln!(out, "SAMPLE RATE:\t{}.000000", session.session_sample_rate)?;
+    if let Some(timeline) = &timeline {
+        writeln!(out, "SESSION START TIMECODE:\t{}", timeline.timecode(0))?;
+        writeln!(out, "TIMECODE FORMAT:\t{}", timecode_format(timeline.timecode_rate()))?;
+    }
+    writeln!(out, "# OF AUDIO TRACKS:\t{}", session.audio_tracks.len())?;
+    writeln!(out, "# OF AUDIO CLIPS:\t{}", session.audio_regions.len())?;
+    writeln!(out, "# OF AUDIO FILES:\t{}", session.audio_files.len())?;
+    writeln!(out, "\n")?;
+
+    writeln!(out, "O N L I N E  F I L E S  I N  S E S S I O N")?;
+    writeln!(out, "Filename \tLocation ")?;
+    for wav in &session.audio_files {
+        writeln!(out, "{}\t", wav.file_name)?;
+    }
+    writeln!(out, "\n")?;
+
+    writeln!(out, "O N L I N E  C L I P S  I N  S E S S I O N")?;
+    writeln!(out, "{:<29}\tSource File", "CLIP NAME")?;
+    for region in &session.audio_regions {
+        let file_name = region.wav.as_ref().map_or("", |wav| &wav.file_name);
+        writeln!(out, "{:<29}\t{}", region.name, file_name)?;
+    }
+    writeln!(out, "\n")?;
+
+    writeln!(out, "T R A C K  L I S T I N G")?;
+    for track in &session.audio_tracks {
+        writeln!(out, "TRACK NAME:\t{}", track.name)?;
+        writeln!(out, "COMMENTS:\t")?;
+        writeln!(out, "USER DELAY:\t0 Samples")?;
+        writeln!(out, "STATE: ")?;
+        writeln!(out, "PLUG-INS: ")?;
+        writeln!(
+            out,
+            "CHANNEL \tEVENT   \t{:<30}\t{:<14}\t{:<14}\t{:<14}\tSTATE",
+            "CLIP NAME", "START TIME", "END TIME", "DURATION"
+        )?;
+        for (channel, regions) in track.channel_regions().into_iter().enumerate() {
+            for (event, region) in regions.iter().enumerate() {
+                let end = region.start_pos + region.len as u64;
+                writeln!(
+                    out,
+                    "{:<8}\t{:<8}\t{:<30}\t{:<14}\t{:<14}\t{:<14}\tUnmuted",
+                    channel + 1,
+                    event + 1,
+                    region.name,
+                    time(region.start_pos),
+                    time(end),
+                    duration(format, timeline.as_ref(), region.start_pos, end)
+                )?;
+            }
+        }
+        writeln!(out)?;
+    }
+    writeln!(out, "\n")?;
+
+    writeln!(out, "M A R K E R S  L I S T I N G")?;
+    writeln!(
+        out,
+        "#   \t{:<13}\t{:<18}\tUNITS    \t{:<33}\tCOMMENTS",
+        "LOCATION", "TIME REFERENCE", "NAME"
+    )?;
+    for marker in &session.markers {
+        let pos = marker.sample_offset as u64;
+        writeln!(
+            out,
+            "{:<4}\t{:<13}\t{:<18}\tSamples  \t{:<33}\t{}",
+            marker.index, time(pos), pos, marker.name, marker.comment
+        )?;
+    }
+    Ok(())
+}
+
+/// The session info text report as a string, see [`write`].
+pub fn to_string(session: &PtSession, name: &str, format: TimeFormat) -> io::Result<String> {
+    export::try_to_string(|out| write(out, session, name, format))
+}
+
+fn section_of(line: &str) -> Option<Section> {
+    let line = line.trim();
+    if line.contains("F I L E S  I N") {
+        Some(Section::Files)
+    } else if line.contains("C L I P S  I N") || line.contains("R E G I O N S  I N") {
+        Some(Section::Clips)
+    } else if line.starts_with("T R A C K  L I S T I N G") {
+        Some(Section::Tracks)
+    } else if line.starts_with("M A R K E R S  L I S T I N G") {
+        Some(Section::Markers)
+    } else if line.ends_with("L I S T I N G") {
+        Some(Section::Other)
+    } else {
+        None
+    }
+}
+
+fn parse_header<'a>(session: &mut PtSession, start: &mut Option<&'a str>, fields: &[&'a str]) -> LineResult<()> {
+    let value = fields.get(1).copied().unwrap_or("");
+    match fields[0] {
+        "SAMPLE RATE:" => {
+            session.session_sample_rate = value.parse::<f64>().map_err(|_| "Invalid sample rate")? as u64;
+        }
+        "TIMECODE FORMAT:" => {
+            session.timecode_rate = Some(timecode_rate(value).ok_or("Unknown timecode format")?);
+        }
+        "SESSION START TIMECODE:" => *start = Some(value),
+        _ => {}
+    }
+    Ok(())
+}
+
+struct Event {
+    channel: usize,
+    name: String,
+    start: u64,
+    len: u64,
+}
+
+impl Event {
+    fn parse(columns: &[String], fields: &[&str], session: &PtSession) -> LineResult<Self> {
+        let field = |names: &[&str]| {
+            columns
+                .iter()
+                .position(|column| names.contains(&column.as_str()))
+                .and_then(|i| fields.get(i).copied())
+                .ok_or("Missing event column")
+        };
+
+        let channel = field(&["CHANNEL"])?.parse().map_err(|_| "Invalid channel")?;
+        let start = parse_time(field(&["START TIME"])?, session)?;
+        let end = parse_time(field(&["END TIME"])?, session)?;
+        if channel == 0 || channel > MAX_CHANNELS {
+            return Err("Invalid channel");
+        }
+        if end < start {
+            return Err("Event ends before it starts");
+        }
+
+        Ok(Event {
+            channel,
+            name: field(&["CLIP NAME", "REGION NAME"])?.to_string(),
+            start,
+            len: end - start,
+        })
+    }
+}
+
+fn parse_marker(columns: &[String], fields: &[&str], session: &PtSession) -> LineResult<Marker> {
+    let field = |name: &str| {
+        columns
+            .iter()
+            .position(|column| column == name)
+            .and_then(|i| fields.get(i).copied())
+    };
+
+    // The time reference is in samples, the location in the report's time format
+    let pos = match (field("TIME REFERENCE"), field("UNITS")) {
+        (Some(samples), Some("Samples")) => samples.parse().map_err(|_| "Invalid time reference")?,
+        _ => parse_time(field("LOCATION").ok_or("Missing marker location")?, session)?,
+    };
+
+    Ok(Marker {
+        name: field("NAME").unwrap_or("").to_string(),
+        index: field("#").and_then(|index| index.parse().ok()).unwrap_or(0),
+        comment: field("COMMENTS").unwrap_or("").to_string(),
+        sample_offset: pos as usize,
+        ..Default::default()
+    })
+}
+
+// Samples, timecode, min:secs or feet+frames. Timecode needs the format and start of the session.
+fn parse_time(s: &str, session: &PtSession) -> LineResult<u64> {
+    let rate = session.session_sample_rate as f64;
+    let s = s.trim();
+
+    if let Ok(samples) = s.parse() {
+        return Ok(samples);
+    }
+    if let Some((feet, frames)) = s.split_once('+') {
+        let feet: u64 = feet.parse().map_err(|_| "Invalid time")?;
+        let frames: f64 = frames.parse().map_err(|_| "Invalid time")?;
+        return Ok(((feet as f64 * 16.0 + frames) * rate / 24.0).round() as u64);
+    }
+    if let Ok(timeline) = session.timeline() {
+        if let Some(timecode) = Timecode::parse(s, timeline.timecode_rate()) {
+            return timeline.position(&timecode).ok_or("Invalid time");
+        }
+    }
+    if let Some((minutes, seconds)) = s.split_once(':') {
+        let minutes: u64 = minutes.parse().map_err(|_| "Invalid time")?;
+        let seconds: f64 = seconds.parse().map_err(|_| "Invalid time")?;
+        return Ok(((minutes as f64 * 60.0 + seconds) * rate).round() as u64);
+    }
+    Err("Invalid time")
+}
+
+fn duration(format: TimeFormat, timeline: Option<&Timeline>, start: u64, end: u64) -> String {
+    match (format, timeline) {
+        (TimeFormat::Timecode, Some(timeline)) => timeline.with_start(0).timecode(end - start).to_string(),
+        (TimeFormat::MinSecs, Some(timeline)) => timeline.min_sec(end - start),
+        _ => (end - start).to_string(),
+    }
+}
+
+fn timecode_format(rate: TimecodeRate) -> &'static str {
+    use TimecodeRate::*;
+    match rate {
+        Fps23976 => "23.976 Frame",
+        Fps24 => "24 Frame",
+        Fps25 => "25 Frame",
+        Fps2997Ndf => "29.97 Non-Drop",
+        Fps2997Df => "29.97 Drop Frame",
+        Fps30 => "30 Frame",
+    }
+}
+
+fn timecode_rate(format: &str) -> Option<TimecodeRate> {
+    use TimecodeRate::*;
+    let format = format.trim();
+    let rate = match format.split_whitespace().next()? {
+        "23.976" => Fps23976,
+        "24" => Fps24,
+        "25" => Fps25,
+        "29.97" if format.contains("Drop Frame") => Fps2997Df,
+        "29.97" => Fps2997Ndf,
+        "30" => Fps30,
+        _ => return None,
+    };
+    Some(rate)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::fs::read_to_string;
+
+    #[test]
+    fn parse_report() {
+        let session = parse(&read_to_string("tests/SessionInfoTest.txt").unwrap()).unwrap();
+        assert_eq!(session.session_sample_rate, 48000);
+        assert_eq!(session.timecode_rate, Some(TimecodeRate::Fps25));
+        assert_eq!(session.session_start, Some(48000 * 3600));
+        assert_eq!(session.audio_files.len(), 3);
+        assert_eq!(session.audio_regions[1].name, "Scene 1-02");
+        assert_eq!(session.audio_regions[1].wav.as_ref().unwrap().file_name, "Scene 1.wav");
+
+        assert_eq!(session.audio_tracks.len(), 2);
+        let dx = &session.audio_tracks[0];
+        assert_eq!(dx.name, "DX 1");
+        assert_eq!(dx.regions.len(), 2);
+        assert_eq!(dx.regions[1].start_pos, 48000 * 10);
+        assert_eq!(dx.regions[1].len, 48000 * 2 + 1920 * 12);
+        let mx = &session.audio_tracks[1];
+        assert_eq!(mx.channels.len(), 2);
+        assert_eq!((mx.index, mx.channels[1].index), (1, 2));
+        assert_eq!(mx.channels[1].regions[0].name, "Score.R");
+
+        assert_eq!(session.markers.len(), 2);
+        assert_eq!(session.markers[0].sample_offset, 480000);
+        assert_eq!(session.markers[1].name, "Scene 2");
+        assert_eq!(session.markers[1].comment, "Check sync");
+
+        // Clips of files the report does not list have no audio file
+        let session = parse("O N L I N E  C L I P S  I N  S E S S I O N\nCLIP NAME\tSource File\nRoom Tone\tGone.wav\n").unwrap();
+        assert!(session.audio_regions[0].wav.is_none());
+        assert!(session.to_string().contains("`Room Tone`, r(0), w(-)"));
+    }
+
+    #[test]
+    fn errors() {
+        let text = "SAMPLE RATE:\t48000\n\nT R A C K  L I S T I N G\nTRACK NAME:\tDX\n\
+            CHANNEL\tEVENT\tCLIP NAME\tSTART TIME\tEND TIME\n1\t1\tLine\t100\t50\n";
+        match parse(text) {
+            Err(PtError::Parse(err)) => {
+                assert_eq!(err.section, error::Section::AudioTracks);
+                assert_eq!(err.offset, text.find("1\t1"));
+                assert_eq!(err.message, "Event ends before it starts");
+            }
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        assert!(matches!(parse("SAMPLE RATE:\tfast"), Err(PtError::Parse(err)) if err.section == error::Section::Header));
+
+        for channel in &["17", "4294967296"] {
+            let text = text.replace("1\t1\tLine\t100\t50", &format!("{}\t1\tLine\t50\t100", channel));
+            match parse(&text) {
+                Err(PtError::Parse(err)) => assert_eq!(err.message, "Invalid channel"),
+                other => panic!("Expected a parse error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        // Session files have no timecode rate and start, only samples can be written
+        let session = PtSession::open("tests/RegionTest.ptx").unwrap();
+        let parsed = parse(&to_string(&session, "RegionTest", TimeFormat::Samples).unwrap()).unwrap();
+        let regions = |s: &PtSession| s.audio_tracks
+            .iter()
+            .flat_map(|t| t.regions.iter().map(|r| (r.name.clone(), r.start_pos, r.len)))
+            .collect::<Vec<_>>();
+        assert_eq!(regions(&parsed), regions(&session));
+        assert_eq!(parsed.timecode_rate, None);
+        assert_eq!(to_string(&session, "RegionTest", TimeFormat::Timecode).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+
+        let session = PtSession {
+            timecode_rate: Some(TimecodeRate::Fps30),
+            session_start: Some(0),
+            ..session
+        };
+        for format in [TimeFormat::Samples, TimeFormat::Timecode, TimeFormat::MinSecs] {
+            let text = to_string(&session, "RegionTest", format).unwrap();
+            let parsed = parse(&text).unwrap();
+            assert_eq!(parsed.audio_tracks.len(), session.audio_tracks.len());
+            assert_eq!(parsed.audio_tracks[2].channels.len(), 2);
+            assert_eq!(parsed.audio_tracks[3].regions[0].name, "region_name_region-03");
+            assert_eq!(parsed.audio_regions.len(), session.audio_regions.len());
+            assert_eq!(parsed.audio_files[0].file_name, session.audio_files[0].file_name);
+            assert_eq!(parsed.timecode_rate, Some(TimecodeRate::Fps30));
+        }
+    }
+}
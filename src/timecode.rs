@@ -0,0 +1,355 @@
+//! Sample positions as SMPTE timecode, min:secs, feet+frames and seconds.
+//!
+//! Bars|beats is out of scope, it needs the tempo and meter map of the session, which
+//! is not parsed. The timecode rate and session start are not read from session files
+//! either: ptformat does not read them and the sessions at hand all have the same
+//! setup, so where they are stored could not be found. They come from a session info
+//! report or from the caller, see [`PtSession::timeline`](crate::PtSession::timeline).
+
+use serde::{Serialize, Deserialize};
+
+use std::convert::TryInto;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+/// Timecode frame rates supported by Pro Tools sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub enum TimecodeRate {
+    Fps23976,
+    Fps24,
+    Fps25,
+    Fps2997Ndf,
+    Fps2997Df,
+    Fps30,
+}
+
+impl Default for TimecodeRate {
+    fn default() -> Self {
+        TimecodeRate::Fps30
+    }
+}
+
+impl TimecodeRate {
+    /// Frames counted per timecode second
+    pub fn nominal_fps(&self) -> u64 {
+        use TimecodeRate::*;
+        match self {
+            Fps23976 | Fps24 => 24,
+            Fps25 => 25,
+            Fps2997Ndf | Fps2997Df | Fps30 => 30,
+        }
+    }
+
+    /// Real frames per second as a fraction
+    fn fps(&self) -> (u64, u64) {
+        use TimecodeRate::*;
+        match self {
+            Fps23976 => (24000, 1001),
+            Fps24 => (24, 1),
+            Fps25 => (25, 1),
+            Fps2997Ndf | Fps2997Df => (30000, 1001),
+            Fps30 => (30, 1),
+        }
+    }
+
+    pub fn is_drop_frame(&self) -> bool {
+        *self == TimecodeRate::Fps2997Df
+    }
+}
+
+impl fmt::Display for TimecodeRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use TimecodeRate::*;
+        match self {
+            Fps23976 => write!(f, "23.976"),
+            Fps24 => write!(f, "24"),
+            Fps25 => write!(f, "25"),
+            Fps2997Ndf => write!(f, "29.97"),
+            Fps2997Df => write!(f, "29.97 DF"),
+            Fps30 => write!(f, "30"),
+        }
+    }
+}
+
+impl FromStr for TimecodeRate {
+    type Err = String;
+
+    /// Parses the rates as they are displayed, case and spaces do not matter.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use TimecodeRate::*;
+        let rate: String = s.split_whitespace().collect::<String>().to_ascii_lowercase();
+        match rate.as_str() {
+            "23.976" => Ok(Fps23976),
+            "24" => Ok(Fps24),
+            "25" => Ok(Fps25),
+            "29.97" => Ok(Fps2997Ndf),
+            "29.97df" => Ok(Fps2997Df),
+            "30" => Ok(Fps30),
+            _ => Err(format!("Unknown timecode rate `{}`", s)),
+        }
+    }
+}
+
+/// SMPTE timecode, displayed as `HH:MM:SS:FF` or `HH:MM:SS;FF` when drop frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Serialize, Deserialize)]
+pub struct Timecode {
+    pub hours: u64,
+    pub minutes: u64,
+    pub seconds: u64,
+    pub frames: u64,
+    pub drop_frame: bool,
+}
+
+impl Timecode {
+    /// Timecode of the `frame`th frame counted from 00:00:00:00.
+    pub fn from_frames(frame: u64, rate: TimecodeRate) -> Self {
+        let fps = rate.nominal_fps();
+        let mut frame = frame;
+
+        if rate.is_drop_frame() {
+            // Frame numbers 0 and 1 are skipped every minute, except every tenth minute
+            const FRAMES_PER_MINUTE: u64 = 30 * 60 - 2;
+            const FRAMES_PER_10_MINUTES: u64 = FRAMES_PER_MINUTE * 10 + 2;
+            let tens = frame / FRAMES_PER_10_MINUTES;
+            let rest = frame % FRAMES_PER_10_MINUTES;
+            frame += 18 * tens;
+            if rest > 2 {
+                frame += 2 * ((rest - 2) / FRAMES_PER_MINUTE);
+            }
+        }
+
+        Timecode {
+            hours: frame / (fps * 3600),
+            minutes: frame / (fps * 60) % 60,
+            seconds: frame / fps % 60,
+            frames: frame % fps,
+            drop_frame: rate.is_drop_frame(),
+        }
+    }
+}
+
+impl Timecode {
+    /// Parses `HH:MM:SS:FF`, with `;` or `.` allowed before the frames.
+    /// Timecode runs up to 23:59:59:FF, and drop frame skips frames 0 and 1 of most minutes.
+    pub fn parse(s: &str, rate: TimecodeRate) -> Option<Self> {
+        let fields: Vec<u64> = s
+            .trim()
+            .split([':', ';', '.'])
+            .map(|field| field.parse().ok())
+            .collect::<Option<_>>()?;
+        match fields[..] {
+            [_, minutes, 0, 0 | 1] if rate.is_drop_frame() && minutes % 10 != 0 => None,
+            [hours, minutes, seconds, frames]
+                if hours < 24 && minutes < 60 && seconds < 60 && frames < rate.nominal_fps() => Some(Timecode {
+                hours,
+                minutes,
+                seconds,
+                frames,
+                drop_frame: rate.is_drop_frame(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Number of frames since 00:00:00:00, the inverse of [`Timecode::from_frames`].
+    /// `None` when that does not fit a `u64`.
+    pub fn to_frames(&self, rate: TimecodeRate) -> Option<u64> {
+        let fps = rate.nominal_fps();
+        let minutes = self.hours.checked_mul(60)?.checked_add(self.minutes)?;
+        let frames = minutes
+            .checked_mul(60)?
+            .checked_add(self.seconds)?
+            .checked_mul(fps)?
+            .checked_add(self.frames)?;
+        if rate.is_drop_frame() {
+            Some(frames - 2 * (minutes - minutes / 10))
+        } else {
+            Some(frames)
+        }
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.drop_frame { ';' } else { ':' };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, separator, self.frames
+        )
+    }
+}
+
+/// Why a session has no [`Timeline`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TimelineError {
+    NoSampleRate,
+    NoTimecodeRate,
+    NoSessionStart,
+}
+
+impl fmt::Display for TimelineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimelineError::NoSampleRate => write!(f, "The session has no sample rate"),
+            TimelineError::NoTimecodeRate => write!(f, "The timecode rate of the session is not known"),
+            TimelineError::NoSessionStart => write!(f, "The session start is not known"),
+        }
+    }
+}
+
+impl Error for TimelineError {}
+
+impl From<TimelineError> for io::Error {
+    fn from(err: TimelineError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, err)
+    }
+}
+
+/// Converts sample positions of a session into the time formats Pro Tools displays.
+/// Positions are truncated to the frame, or millisecond, they fall in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Timeline {
+    sample_rate: u64,
+    timecode_rate: TimecodeRate,
+    start: u64,
+}
+
+impl Timeline {
+    /// Film frames per foot of 35mm
+    const FRAMES_PER_FOOT: u64 = 16;
+
+    /// A timeline where position 0 is `start` samples into the timecode day.
+    pub fn new(sample_rate: u64, timecode_rate: TimecodeRate, start: u64) -> Result<Self, TimelineError> {
+        if sample_rate == 0 {
+            return Err(TimelineError::NoSampleRate);
+        }
+        Ok(Timeline { sample_rate, timecode_rate, start })
+    }
+
+    pub fn sample_rate(&self) -> u64 {
+        self.sample_rate
+    }
+
+    pub fn timecode_rate(&self) -> TimecodeRate {
+        self.timecode_rate
+    }
+
+    /// Session start in samples, the timecode of position 0
+    pub fn start(&self) -> u64 {
+        self.start
+    }
+
+    /// The same timeline starting at `start`
+    pub fn with_start(self, start: u64) -> Self {
+        Timeline { start, ..self }
+    }
+
+    pub fn timecode(&self, pos: u64) -> Timecode {
+        let (num, den) = self.timecode_rate.fps();
+        let frame = (pos + self.start) as u128 * num as u128 / (self.sample_rate as u128 * den as u128);
+        Timecode::from_frames(frame as u64, self.timecode_rate)
+    }
+
+    /// Sample position of the start of the frame `timecode`, before the session start it is 0.
+    /// `None` when it does not fit a `u64`.
+    pub fn position(&self, timecode: &Timecode) -> Option<u64> {
+        let (num, den) = self.timecode_rate.fps();
+        let frame = timecode.to_frames(self.timecode_rate)?;
+        let samples = frame as u128 * self.sample_rate as u128 * den as u128;
+        // Round up so the position falls in the frame
+        let samples: u64 = ((samples + num as u128 - 1) / num as u128).try_into().ok()?;
+        Some(samples.saturating_sub(self.start))
+    }
+
+    /// `M:SS.mmm`, counted from the session start
+    pub fn min_sec(&self, pos: u64) -> String {
+        let millis = pos as u128 * 1000 / self.sample_rate as u128;
+        format!("{}:{:02}.{:03}", millis / 60000, millis / 1000 % 60, millis % 1000)
+    }
+
+    /// `F+FF` at 24 frames per second, counted from the session start
+    pub fn feet_frames(&self, pos: u64) -> String {
+        let frame = pos as u128 * 24 / self.sample_rate as u128;
+        let per_foot = Self::FRAMES_PER_FOOT as u128;
+        format!("{}+{:02}", frame / per_foot, frame % per_foot)
+    }
+
+    /// Seconds counted from the session start
+    pub fn seconds(&self, pos: u64) -> f64 {
+        pos as f64 / self.sample_rate as f64
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn timeline(sample_rate: u64, timecode_rate: TimecodeRate) -> Timeline {
+        Timeline::new(sample_rate, timecode_rate, 0).unwrap()
+    }
+
+    #[test]
+    fn timecode() {
+        let hour = 48000 * 3600;
+        assert_eq!(timeline(48000, TimecodeRate::Fps25).timecode(hour + 48000 + 1920).to_string(), "01:00:01:01");
+        assert_eq!(timeline(48000, TimecodeRate::Fps24).timecode(47999).to_string(), "00:00:00:23");
+        assert_eq!(timeline(48000, TimecodeRate::Fps30).timecode(hour).to_string(), "01:00:00:00");
+
+        // 29.97 runs slow, an hour of samples does not reach 01:00:00:00 without drop frame
+        assert_eq!(timeline(48000, TimecodeRate::Fps2997Ndf).timecode(hour).to_string(), "00:59:56:12");
+        assert_eq!(timeline(48000, TimecodeRate::Fps2997Df).timecode(hour).to_string(), "01:00:00;00");
+        assert_eq!(timeline(48000, TimecodeRate::Fps23976).timecode(48048).to_string(), "00:00:01:00");
+
+        let start = timeline(48000, TimecodeRate::Fps25).with_start(hour);
+        assert_eq!(start.timecode(0).to_string(), "01:00:00:00");
+    }
+
+    #[test]
+    fn drop_frame() {
+        let tc = |frame| Timecode::from_frames(frame, TimecodeRate::Fps2997Df).to_string();
+        assert_eq!(tc(1799), "00:00:59;29");
+        assert_eq!(tc(1800), "00:01:00;02");
+        assert_eq!(tc(17981), "00:09:59;29");
+        assert_eq!(tc(17982), "00:10:00;00");
+        assert_eq!(tc(17984), "00:10:00;02");
+    }
+
+    #[test]
+    fn parse() {
+        let t = timeline(48000, TimecodeRate::Fps2997Df).with_start(48000 * 3600);
+        for pos in [0, 1601, 48000 * 60, 48000 * 600 + 17] {
+            let tc = t.timecode(pos);
+            assert_eq!(Timecode::parse(&tc.to_string(), t.timecode_rate()), Some(tc));
+            assert_eq!(t.timecode(t.position(&tc).unwrap()), tc);
+        }
+        assert_eq!(Timecode::parse("00:00:00:30", TimecodeRate::Fps30), None);
+        assert_eq!(Timecode::parse("1:00", TimecodeRate::Fps30), None);
+        assert_eq!(Timecode::parse("24:00:00:00", TimecodeRate::Fps30), None);
+
+        assert_eq!(Timecode::parse("00:01:00;00", TimecodeRate::Fps2997Df), None);
+        assert!(Timecode::parse("00:10:00;00", TimecodeRate::Fps2997Df).is_some());
+
+        // Timecode past any sample position
+        let far = Timecode { hours: u64::MAX / 60, minutes: 0, seconds: 0, frames: 0, drop_frame: false };
+        assert_eq!(far.to_frames(TimecodeRate::Fps30), None);
+        assert_eq!(Timeline::new(0, TimecodeRate::Fps30, 0), Err(TimelineError::NoSampleRate));
+
+        for rate in [TimecodeRate::Fps23976, TimecodeRate::Fps2997Ndf, TimecodeRate::Fps2997Df] {
+            assert_eq!(rate.to_string().parse(), Ok(rate));
+        }
+        assert!("29".parse::<TimecodeRate>().is_err());
+    }
+
+    #[test]
+    fn other_formats() {
+        let t = timeline(44100, TimecodeRate::Fps30);
+        assert_eq!(t.min_sec(44100 * 61 + 441), "1:01.010");
+        assert_eq!(t.feet_frames(44100), "1+08");
+        assert_eq!(t.seconds(22050), 0.5);
+    }
+}
@@ -0,0 +1,99 @@
+use std::process::Command;
+
+fn ptsession(args: &[&str]) -> std::process::Output {
+    Command::new(env!("CARGO_BIN_EXE_ptsession"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn info() {
+    let output = ptsession(&["info", "tests/RegionTest.ptx"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        std::fs::read_to_string("tests/RegionTestOutput.txt").unwrap()
+    );
+}
+
+#[test]
+fn markers() {
+    let output = ptsession(&["markers", "tests/MarkerTest.ptx"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert_eq!(stdout.lines().next(), Some("1\t2490368\tThisIsMarker1\tThisIsMarker1"));
+    assert_eq!(stdout.lines().count(), 8);
+}
+
+#[test]
+fn exit_codes() {
+    assert_eq!(ptsession(&[]).status.code(), Some(2));
+    assert_eq!(ptsession(&["frobnicate", "tests/RegionTest.ptx"]).status.code(), Some(2));
+    assert_eq!(ptsession(&["info", "tests/DoesNotExist.ptx"]).status.code(), Some(3));
+    assert_eq!(ptsession(&["info", "tests/RegionTestOutput.txt"]).status.code(), Some(4));
+    // Documents are written for one session only
+    assert_eq!(ptsession(&["otio", "tests/RegionTest.ptx", "tests/MarkerTest.ptx"]).status.code(), Some(2));
+
+    // Later failures do not hide the output of valid sessions
+    let output = ptsession(&["files", "tests/RegionTest.ptx", "tests/DoesNotExist.ptx"]);
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stdout).unwrap().contains("region_name_WAV.wav"));
+}
+
+#[test]
+fn timecode() {
+    // Sessions do not store a timecode rate and start that is read
+    assert_eq!(ptsession(&["edl", "tests/RegionTest.ptx"]).status.code(), Some(2));
+    assert_eq!(ptsession(&["--start", "01:00:00:00", "edl", "tests/RegionTest.ptx"]).status.code(), Some(2));
+
+    // Session info reports are written in samples without them
+    let output = ptsession(&["text", "tests/RegionTest.ptx"]);
+    assert!(output.status.success());
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("TIMECODE FORMAT"));
+    let output = ptsession(&["--fps", "25", "--start", "01:00:00:00", "text", "tests/RegionTest.ptx"]);
+    assert!(String::from_utf8(output.stdout).unwrap().contains("SESSION START TIMECODE:\t01:00:00:00"));
+
+    let output = ptsession(&["--fps", "25", "--start", "01:00:00:00", "edl", "tests/RegionTest.ptx"]);
+    // The session has several tracks
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--track: Track_Name, Audio 1, Audio 2, Audio 3"));
+
+    let output = ptsession(&["--fps", "25", "--start", "01:00:00:00", "--track", "Track_Name", "edl", "tests/RegionTest.ptx"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.lines().any(|line| line.starts_with("001 ") && line.ends_with("01:00:00:00 01:01:00:02")));
+}
+
+#[test]
+fn diff() {
+    let output = ptsession(&["diff", "tests/RegionTest.ptx", "tests/RegionTest.ptx"]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = ptsession(&["diff", "tests/RegionTest.ptx", "tests/MarkerTest.ptx"]);
+    assert_eq!(output.status.code(), Some(1));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("- file `region_name_WAV.wav`"));
+    assert!(stdout.contains("+ marker 1 `ThisIsMarker1`"));
+
+    assert_eq!(ptsession(&["diff", "tests/RegionTest.ptx"]).status.code(), Some(2));
+}
+
+#[test]
+fn media() {
+    let output = ptsession(&["media", "tests/RegionTest.ptx"]);
+    assert_eq!(output.status.code(), Some(7));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "Missing\tregion_name_WAV.wav\n");
+
+    let dir = std::env::temp_dir().join(format!("ptsession-media-{}", std::process::id()));
+    std::fs::create_dir_all(dir.join("Renders")).unwrap();
+    std::fs::write(dir.join("Renders").join("region_name_WAV.wav"), b"").unwrap();
+    let output = ptsession(&["--search", dir.to_str().unwrap(), "media", "tests/RegionTest.ptx"]);
+    std::fs::remove_dir_all(&dir).unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("Found\tregion_name_WAV.wav\t{}\n", dir.join("Renders").join("region_name_WAV.wav").display())
+    );
+}